@@ -0,0 +1,49 @@
+//! Parsing of `#[ruby(...)]` attributes.
+
+use syn::{Attribute, LitBool, LitStr, Result};
+
+/// The options set by `#[ruby(...)]` attributes on an item.
+#[derive(Default)]
+pub struct Options {
+    pub name: Option<String>,
+    pub reader: bool,
+    pub writer: bool,
+    pub skip: bool,
+    /// Set by `typed_data = false` to implement `TypedData` by hand.
+    pub manual_typed_data: bool,
+}
+
+impl Options {
+    /// Parses every `#[ruby(...)]` attribute in a list.
+    pub fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Options::default();
+
+        for attr in attrs.iter().filter(|attr| is_ruby(attr)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("reader") {
+                    options.reader = true;
+                } else if meta.path.is_ident("writer") {
+                    options.writer = true;
+                } else if meta.path.is_ident("accessor") {
+                    options.reader = true;
+                    options.writer = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else if meta.path.is_ident("typed_data") {
+                    options.manual_typed_data = !meta.value()?.parse::<LitBool>()?.value;
+                } else {
+                    return Err(meta.error("unknown ruby option"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// Checks if an attribute is `#[ruby(...)]`.
+pub fn is_ruby(attr: &Attribute) -> bool {
+    attr.path().is_ident("ruby")
+}
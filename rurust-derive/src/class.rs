@@ -0,0 +1,71 @@
+//! `#[derive(RubyClass)]`.
+
+use crate::attr::Options;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Result};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let options = Options::parse(&input.attrs)?;
+    let ident = &input.ident;
+    let name = options.name.unwrap_or_else(|| ident.to_string());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "RubyClass needs a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "RubyClass can only be derived for structs")),
+    };
+
+    let mut attributes = Vec::new();
+    for field in fields {
+        let options = Options::parse(&field.attrs)?;
+        let field_ident = field.ident.as_ref().unwrap();
+        let field_name = options.name.unwrap_or_else(|| field_ident.to_string());
+
+        if options.reader {
+            attributes.push(quote! {
+                let class = class.method_fn(#field_name, |receiver, args| {
+                    ::rurust::derive::__private::check_arity(args, 0)?;
+                    let this = ::rurust::derive::__private::borrow::<Self>(&receiver)?;
+                    Ok(::rurust::IntoValue::into_value(::std::clone::Clone::clone(&this.#field_ident)))
+                });
+            });
+        }
+
+        if options.writer {
+            let writer_name = format!("{}=", field_name);
+            attributes.push(quote! {
+                let class = class.method_fn(#writer_name, |receiver, args| {
+                    ::rurust::derive::__private::check_arity(args, 1)?;
+                    let value = ::rurust::TryFromValue::try_from_value(args[0])?;
+                    ::rurust::derive::__private::borrow_mut::<Self>(&receiver)?.#field_ident = value;
+                    Ok(args[0])
+                });
+            });
+        }
+    }
+
+    // Types holding Ruby values implement `TypedData` themselves to mark them.
+    let typed_data = if options.manual_typed_data {
+        quote! { }
+    } else {
+        quote! { impl #impl_generics ::rurust::TypedData for #ident #ty_generics #where_clause { } }
+    };
+
+    Ok(quote! {
+        #typed_data
+
+        impl #impl_generics ::rurust::RubyClass for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+
+            fn define_attributes(class: ::rurust::builder::Class) -> ::rurust::builder::Class {
+                #( #attributes )*
+                class
+            }
+        }
+    })
+}
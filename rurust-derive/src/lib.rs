@@ -0,0 +1,53 @@
+//! Derive macros for exposing Rust types as Ruby classes.
+//!
+//! Use these through the `derive` feature of `rurust`, which re-exports them.
+
+extern crate proc_macro;
+
+mod attr;
+mod class;
+mod methods;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemImpl};
+
+/// Exposes a struct as a Ruby class.
+///
+/// Wraps the struct with `TypedData` and defines attribute methods for its
+/// fields. The class is named after the struct unless renamed with
+/// `#[ruby(name = "...")]`.
+///
+/// Mark fields with `#[ruby(reader)]`, `#[ruby(writer)]` or `#[ruby(accessor)]`.
+/// Readers need the field to be `Clone + IntoValue`, writers `TryFromValue`.
+///
+/// Structs holding Ruby values must mark them for the garbage collector.
+/// Use `#[ruby(typed_data = false)]` on the struct to skip the empty
+/// `TypedData` impl and write one with a `mark` hook instead.
+#[proc_macro_derive(RubyClass, attributes(ruby))]
+pub fn derive_ruby_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    class::expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Exposes the public functions of an `impl` block as Ruby methods.
+///
+/// Functions taking `&self` or `&mut self` become instance methods, others
+/// singleton methods, and a `new` function returning `Self` becomes
+/// `initialize`. Arguments are converted with `TryFromValue` and return
+/// values with `IntoValue`, and may be wrapped in a `Result` whose error
+/// converts into `ErrorKind`.
+///
+/// Rename a method with `#[ruby(name = "...")]`, or leave a public
+/// function out with `#[ruby(skip)]`.
+#[proc_macro_attribute]
+pub fn ruby_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return syn::Error::new_spanned(attr, "ruby_methods takes no arguments")
+            .into_compile_error().into();
+    }
+    let item = parse_macro_input!(item as ItemImpl);
+
+    methods::expand(item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
@@ -0,0 +1,169 @@
+//! `#[ruby_methods]`.
+
+use crate::attr::{self, Options};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Error, FnArg, GenericArgument, Ident, ImplItem, ImplItemFn, ItemImpl, PathArguments, Result,
+          ReturnType, Type, Visibility};
+
+pub fn expand(mut item: ItemImpl) -> Result<TokenStream> {
+    if let Some((_, ref trait_path, _)) = item.trait_ {
+        return Err(Error::new_spanned(trait_path, "ruby_methods must be used on an inherent impl"));
+    }
+
+    let self_ident = match *item.self_ty {
+        Type::Path(ref path) => path.path.segments.last().map(|segment| segment.ident.clone()),
+        _ => None,
+    };
+
+    let mut methods = Vec::new();
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Fn(ref mut function) = *impl_item {
+            let options = Options::parse(&function.attrs)?;
+            function.attrs.retain(|attr| !attr::is_ruby(attr));
+
+            if let Visibility::Public(..) = function.vis {
+                if !options.skip {
+                    methods.push(method(function, options, self_ident.as_ref())?);
+                }
+            }
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::rurust::RubyMethods for #self_ty #where_clause {
+            fn define_methods(class: ::rurust::builder::Class) -> ::rurust::builder::Class {
+                #( #methods )*
+                class
+            }
+        }
+    })
+}
+
+/// Generates the definition of a single method.
+fn method(function: &ImplItemFn, options: Options, self_ident: Option<&Ident>) -> Result<TokenStream> {
+    let sig = &function.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(&sig.generics, "Ruby methods cannot be generic"));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(Error::new_spanned(asyncness, "Ruby methods cannot be async"));
+    }
+
+    let ident = &sig.ident;
+    let mut receiver = None;
+    let mut conversions = Vec::new();
+    let mut arguments = Vec::new();
+
+    for input in sig.inputs.iter() {
+        match *input {
+            FnArg::Receiver(ref r) => {
+                if r.reference.is_none() {
+                    return Err(Error::new_spanned(r, "Ruby methods must take `&self` or `&mut self`"));
+                }
+                receiver = Some(r.mutability.is_some());
+            },
+            FnArg::Typed(ref typed) => {
+                if let Type::Reference(..) = *typed.ty {
+                    return Err(Error::new_spanned(&typed.ty, "Ruby method arguments must be owned"));
+                }
+
+                let index = arguments.len();
+                let var = format_ident!("__arg{}", index);
+                let ty = &typed.ty;
+                conversions.push(quote! {
+                    let #var: #ty = ::rurust::TryFromValue::try_from_value(args[#index])?;
+                });
+                arguments.push(var);
+            },
+        }
+    }
+
+    // Methods returning `Self` wrap the value in a new object of the receiver's class.
+    let returns_self = match sig.output {
+        ReturnType::Type(_, ref ty) => is_self(ty, self_ident),
+        ReturnType::Default => false,
+    };
+    let into_return = |class: TokenStream| if returns_self {
+        quote! { ::rurust::derive::__private::Wrapped::into_wrapped(result, #class) }
+    } else {
+        quote! { ::rurust::derive::__private::MethodReturn::into_return(result) }
+    };
+
+    let arity = arguments.len();
+    let prelude = quote! {
+        ::rurust::derive::__private::check_arity(args, #arity)?;
+        #( #conversions )*
+    };
+
+    let tokens = match receiver {
+        None if ident == "new" => quote! {
+            let class = class.method_fn("initialize", |receiver, args| {
+                #prelude
+                let value = <_ as ::rurust::derive::__private::Constructed<Self>>::into_constructed(
+                    Self::#ident(#( #arguments ),*))?;
+                ::rurust::typed_data::set(receiver, value)?;
+                Ok(::rurust::Value::nil())
+            });
+        },
+        None => {
+            let name = options.name.unwrap_or_else(|| ident.to_string());
+            let into_return = into_return(quote! { receiver });
+            quote! {
+                let class = class.singleton_method_fn(#name, |receiver, args| {
+                    #prelude
+                    let result = Self::#ident(#( #arguments ),*);
+                    #into_return
+                });
+            }
+        },
+        Some(mutable) => {
+            let name = options.name.unwrap_or_else(|| ident.to_string());
+            let this = if mutable {
+                quote! { &mut *::rurust::derive::__private::borrow_mut::<Self>(&receiver)? }
+            } else {
+                quote! { &*::rurust::derive::__private::borrow::<Self>(&receiver)? }
+            };
+
+            let into_return = into_return(quote! { ::rurust::derive::__private::class_of(receiver) });
+            quote! {
+                let class = class.method_fn(#name, |receiver, args| {
+                    #prelude
+                    let result = Self::#ident(#this, #( #arguments ),*);
+                    #into_return
+                });
+            }
+        },
+    };
+    Ok(tokens)
+}
+
+/// Checks if a return type is `Self` or `Result<Self, _>`.
+fn is_self(ty: &Type, self_ident: Option<&Ident>) -> bool {
+    let path = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => &path.path,
+        _ => return false,
+    };
+    let segment = match path.segments.last() {
+        Some(segment) => segment,
+        None => return false,
+    };
+
+    if path.is_ident("Self") || Some(&segment.ident) == self_ident {
+        return true;
+    }
+
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if segment.ident == "Result" => match args.args.first() {
+            Some(GenericArgument::Type(ref ty)) => is_self(ty, self_ident),
+            _ => false,
+        },
+        _ => false,
+    }
+}
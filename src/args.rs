@@ -0,0 +1,219 @@
+//! Argument specifications for closure-based methods.
+//!
+//! An `ArgSpec` describes the arguments a method takes, much like the
+//! format string given to `rb_scan_args` and the keyword table given to
+//! `rb_get_kwargs`. Calls are checked against it before the closure runs,
+//! raising `ArgumentError` with Ruby's usual messages on a mismatch.
+
+use crate::{block, classes, ffi, ErrorKind, RHash, RootedValue, RubyException, Value};
+
+/// The arguments a method takes.
+///
+/// Arguments are taken in the order required, optional, splat, then
+/// keywords and the block.
+#[derive(Default)]
+pub struct ArgSpec {
+    required: usize,
+    optional: usize,
+    splat: bool,
+    keywords: Vec<Keyword>,
+    keyword_rest: bool,
+    block: bool,
+}
+
+/// A keyword argument.
+struct Keyword {
+    name: String,
+    /// The value when the keyword is omitted. Required if `None`.
+    default: Option<RootedValue>,
+}
+
+/// The arguments of a call, parsed according to an `ArgSpec`.
+pub struct Args {
+    required: Vec<Value>,
+    optional: Vec<Value>,
+    splat: Vec<Value>,
+    keywords: Vec<(String, Value)>,
+    keyword_rest: Option<RHash>,
+    /// The block is a new object that is only referenced from here.
+    block: Option<RootedValue>,
+}
+
+impl ArgSpec {
+    /// Creates a spec for a method taking no arguments.
+    pub fn new() -> Self {
+        ArgSpec::default()
+    }
+
+    /// Sets the number of required positional arguments.
+    pub fn required(mut self, count: usize) -> Self {
+        self.required = count;
+        self
+    }
+
+    /// Sets the number of optional positional arguments.
+    pub fn optional(mut self, count: usize) -> Self {
+        self.optional = count;
+        self
+    }
+
+    /// Collects any remaining positional arguments, like `*rest`.
+    pub fn splat(mut self) -> Self {
+        self.splat = true;
+        self
+    }
+
+    /// Adds a required keyword argument.
+    pub fn keyword<S>(mut self, name: S) -> Self
+        where S: Into<String> {
+        self.keywords.push(Keyword { name: name.into(), default: None });
+        self
+    }
+
+    /// Adds an optional keyword argument.
+    ///
+    /// The same default object is used for every call, so it should not be mutated.
+    pub fn keyword_default<S>(mut self, name: S, default: Value) -> Self
+        where S: Into<String> {
+        self.keywords.push(Keyword { name: name.into(), default: Some(RootedValue::new(default)) });
+        self
+    }
+
+    /// Collects any unknown keywords, like `**rest`.
+    pub fn keyword_rest(mut self) -> Self {
+        self.keyword_rest = true;
+        self
+    }
+
+    /// Captures the block as a `Proc`, like `&block`.
+    pub fn block(mut self) -> Self {
+        self.block = true;
+        self
+    }
+
+    fn takes_keywords(&self) -> bool {
+        !self.keywords.is_empty() || self.keyword_rest
+    }
+
+    /// Parses the arguments of the method call currently executing.
+    pub(crate) fn parse(&self, args: &[Value]) -> Result<Args, ErrorKind> {
+        let mut positional = args;
+        let mut given_keywords = None;
+
+        if self.takes_keywords() && unsafe { ffi::rb_keyword_given_p() } != 0 {
+            if let Some((last, rest)) = args.split_last() {
+                given_keywords = RHash::from_value(*last);
+                positional = rest;
+            }
+        }
+
+        self.check_arity(positional.len())?;
+
+        let optional = (positional.len() - self.required).min(self.optional);
+        let (required, rest) = positional.split_at(self.required);
+        let (optional, splat) = rest.split_at(optional);
+
+        let mut keywords = Vec::new();
+        let mut missing = Vec::new();
+        for keyword in self.keywords.iter() {
+            let value = given_keywords.and_then(|hash| hash.get(Value::symbol(&keyword.name)));
+
+            match (value, &keyword.default) {
+                (Some(value), _) => keywords.push((keyword.name.clone(), value)),
+                (None, Some(default)) => keywords.push((keyword.name.clone(), default.get())),
+                (None, None) => missing.push(format!(":{}", keyword.name)),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(argument_error(list_message("missing keyword", &missing)));
+        }
+
+        let mut keyword_rest = None;
+        if let Some(hash) = given_keywords {
+            let unknown: Vec<_> = hash.iter().filter(|&(key, _)| !self.is_keyword(key)).collect();
+
+            if self.keyword_rest {
+                keyword_rest = Some(unknown.into_iter().collect());
+            } else if !unknown.is_empty() {
+                let names: Vec<_> = unknown.iter().map(|(key, _)| key.inspect_string()).collect();
+                return Err(argument_error(list_message("unknown keyword", &names)));
+            }
+        }
+
+        Ok(Args {
+            required: required.to_vec(),
+            optional: optional.to_vec(),
+            splat: splat.to_vec(),
+            keywords,
+            keyword_rest,
+            block: if self.block { block::block_proc().map(RootedValue::new) } else { None },
+        })
+    }
+
+    fn check_arity(&self, given: usize) -> Result<(), ErrorKind> {
+        let max = self.required + self.optional;
+        if given >= self.required && (self.splat || given <= max) {
+            return Ok(());
+        }
+
+        let expected = if self.splat {
+            format!("{}+", self.required)
+        } else if self.optional > 0 {
+            format!("{}..{}", self.required, max)
+        } else {
+            self.required.to_string()
+        };
+        Err(argument_error(format!("wrong number of arguments (given {}, expected {})", given, expected)))
+    }
+
+    fn is_keyword(&self, key: Value) -> bool {
+        key.is_symbol() && {
+            let name = key.display_string();
+            self.keywords.iter().any(|keyword| keyword.name == name)
+        }
+    }
+}
+
+impl Args {
+    /// Gets a required positional argument.
+    ///
+    /// Panics if the spec has fewer required arguments.
+    pub fn required(&self, index: usize) -> Value {
+        self.required[index]
+    }
+
+    /// Gets an optional positional argument, if it was given.
+    pub fn optional(&self, index: usize) -> Option<Value> {
+        self.optional.get(index).cloned()
+    }
+
+    /// Gets the positional arguments collected by the splat.
+    pub fn splat(&self) -> &[Value] {
+        &self.splat
+    }
+
+    /// Gets a keyword argument, or its default if it was not given.
+    pub fn keyword(&self, name: &str) -> Option<Value> {
+        self.keywords.iter().find(|&(n, _)| n == name).map(|&(_, value)| value)
+    }
+
+    /// Gets the unknown keywords, if the spec collects them.
+    pub fn keyword_rest(&self) -> Option<RHash> {
+        self.keyword_rest
+    }
+
+    /// Gets the block, if the spec captures it and one was given.
+    pub fn block(&self) -> Option<Value> {
+        self.block.as_ref().map(RootedValue::get)
+    }
+}
+
+fn argument_error(message: String) -> ErrorKind {
+    RubyException::new(classes::ArgError(), message).into()
+}
+
+/// Formats a message like `missing keywords: :a, :b`.
+fn list_message(prefix: &str, items: &[String]) -> String {
+    let plural = if items.len() == 1 { "" } else { "s" };
+    format!("{}{}: {}", prefix, plural, items.join(", "))
+}
@@ -0,0 +1,173 @@
+//! Ruby arrays.
+
+use crate::{ffi, libc, ConversionError, IntoValue, TryFromValue, Value};
+
+use std::iter::FromIterator;
+use std::fmt;
+
+/// A Ruby `Array`.
+///
+/// Like `Value`, this is a plain reference that does not keep the array alive.
+///
+/// Ruby raises `FrozenError` straight through the caller if a frozen
+/// array is modified.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct RArray(Value);
+
+/// An iterator over the elements of an array.
+pub struct Iter {
+    array: RArray,
+    index: usize,
+}
+
+impl RArray {
+    /// Creates an empty array.
+    pub fn new() -> Self {
+        RArray(Value::from(unsafe { ffi::rb_ary_new() }))
+    }
+
+    /// Creates an empty array with room for `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        RArray(Value::from(unsafe { ffi::rb_ary_new_capa(capacity as libc::c_long) }))
+    }
+
+    /// Views a value as an array.
+    ///
+    /// Returns `None` if the value is not an `Array`.
+    pub fn from_value(value: Value) -> Option<Self> {
+        if value.is_array() { Some(RArray(value)) } else { None }
+    }
+
+    /// Gets the underlying array object.
+    pub fn value(&self) -> Value { self.0 }
+
+    /// Gets the number of elements.
+    pub fn len(&self) -> usize {
+        unsafe { ffi::RARRAY_LEN(self.0 .0) as usize }
+    }
+
+    /// Checks if the array has no elements.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Appends an element.
+    pub fn push<T>(&self, value: T) where T: IntoValue {
+        unsafe { ffi::rb_ary_push(self.0 .0, value.into_value().0) };
+    }
+
+    /// Removes the last element.
+    pub fn pop(&self) -> Option<Value> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Value::from(unsafe { ffi::rb_ary_pop(self.0 .0) }))
+        }
+    }
+
+    /// Gets an element.
+    pub fn get(&self, index: usize) -> Option<Value> {
+        if index < self.len() {
+            Some(Value::from(unsafe { ffi::rb_ary_entry(self.0 .0, index as libc::c_long) }))
+        } else {
+            None
+        }
+    }
+
+    /// Sets an element.
+    ///
+    /// Setting past the end pads the array with `nil`, as in Ruby.
+    pub fn set<T>(&self, index: usize, value: T) where T: IntoValue {
+        unsafe { ffi::rb_ary_store(self.0 .0, index as libc::c_long, value.into_value().0) };
+    }
+
+    /// Iterates over the elements.
+    ///
+    /// The array may be modified during iteration; the iterator stops
+    /// once it runs past the end.
+    pub fn iter(&self) -> Iter {
+        Iter { array: *self, index: 0 }
+    }
+
+    /// Copies the elements into a `Vec`.
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.iter().collect()
+    }
+
+    /// Converts every element into a Rust value.
+    pub fn try_to_vec<T>(&self) -> Result<Vec<T>, ConversionError> where T: TryFromValue {
+        self.iter().map(T::try_from_value).collect()
+    }
+}
+
+impl Default for RArray {
+    fn default() -> Self { RArray::new() }
+}
+
+impl Iterator for Iter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let element = self.array.get(self.index)?;
+        self.index += 1;
+        Some(element)
+    }
+}
+
+impl IntoIterator for RArray {
+    type Item = Value;
+    type IntoIter = Iter;
+
+    fn into_iter(self) -> Iter { self.iter() }
+}
+
+impl<T> FromIterator<T> for RArray where T: IntoValue {
+    fn from_iter<I>(iter: I) -> Self where I: IntoIterator<Item=T> {
+        let iter = iter.into_iter();
+        let array = RArray::with_capacity(iter.size_hint().0);
+
+        for element in iter {
+            array.push(element);
+        }
+        array
+    }
+}
+
+impl<T> From<Vec<T>> for RArray where T: IntoValue {
+    fn from(elements: Vec<T>) -> Self {
+        elements.into_iter().collect()
+    }
+}
+
+impl<T> From<&[T]> for RArray where T: IntoValue + Clone {
+    fn from(elements: &[T]) -> Self {
+        elements.iter().cloned().collect()
+    }
+}
+
+impl From<RArray> for Vec<Value> {
+    fn from(array: RArray) -> Self {
+        array.to_vec()
+    }
+}
+
+impl From<RArray> for Value {
+    fn from(array: RArray) -> Self {
+        array.0
+    }
+}
+
+impl IntoValue for RArray {
+    fn into_value(self) -> Value { self.0 }
+}
+
+impl TryFromValue for RArray {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RArray::from_value(value).ok_or_else(|| ConversionError::wrong_type("Array", value))
+    }
+}
+
+impl fmt::Debug for RArray {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, fmt)
+    }
+}
@@ -0,0 +1,63 @@
+//! Conversions between Ruby `Integer`s and `num-bigint` types.
+
+use crate::convert::INTEGER_PACK_FLAGS;
+use crate::{ffi, libc, ConversionError, IntoValue, TryFromValue, Value};
+
+use num_bigint::{BigInt, BigUint, Sign};
+
+use std::ptr;
+
+impl IntoValue for BigInt {
+    fn into_value(self) -> Value {
+        unpack(&self.to_signed_bytes_le(), INTEGER_PACK_FLAGS | ffi::INTEGER_PACK_2COMP)
+    }
+}
+
+impl TryFromValue for BigInt {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        let (sign, magnitude) = pack(value)?;
+        Ok(BigInt::from_bytes_le(sign, &magnitude))
+    }
+}
+
+impl IntoValue for BigUint {
+    fn into_value(self) -> Value {
+        unpack(&self.to_bytes_le(), INTEGER_PACK_FLAGS)
+    }
+}
+
+impl TryFromValue for BigUint {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        match pack(value)? {
+            (Sign::Minus, _) => Err(ConversionError::OutOfRange { target: "BigUint" }),
+            (_, magnitude) => Ok(BigUint::from_bytes_le(&magnitude)),
+        }
+    }
+}
+
+fn unpack(bytes: &[u8], flags: libc::c_int) -> Value {
+    Value::from(unsafe {
+        ffi::rb_integer_unpack(bytes.as_ptr() as *const libc::c_void, bytes.len(), 1, 0, flags)
+    })
+}
+
+/// Gets the sign and little-endian magnitude of an `Integer`.
+fn pack(value: Value) -> Result<(Sign, Vec<u8>), ConversionError> {
+    if !value.is_integer() {
+        return Err(ConversionError::wrong_type("Integer", value));
+    }
+
+    let len = unsafe { ffi::rb_absint_size(value.0, ptr::null_mut()) };
+    let mut magnitude = vec![0u8; len.max(1)];
+    let sign = unsafe {
+        ffi::rb_integer_pack(value.0, magnitude.as_mut_ptr() as *mut libc::c_void, magnitude.len(), 1, 0,
+                             INTEGER_PACK_FLAGS)
+    };
+
+    let sign = match sign {
+        s if s < 0 => Sign::Minus,
+        0 => Sign::NoSign,
+        _ => Sign::Plus,
+    };
+    Ok((sign, magnitude))
+}
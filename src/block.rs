@@ -0,0 +1,125 @@
+//! Ruby blocks.
+//!
+//! The functions here refer to the block passed to the Rust method that
+//! Ruby is currently executing, so they are only meaningful inside
+//! closure-based methods.
+
+use crate::{callback, classes, ffi, libc, typed_data, ErrorKind, RubyException, TypedData, Value, VM};
+
+use std::cell::RefCell;
+use std::slice;
+
+/// A Rust closure callable as a Ruby `Proc`.
+///
+/// Takes the arguments the proc was called with.
+pub type ProcFn = dyn Fn(&[Value]) -> Result<Value, ErrorKind>;
+
+/// The closure behind a `Proc`, owned by a Ruby object so that it is
+/// dropped when the proc is garbage collected.
+struct ProcBody(Box<ProcFn>);
+
+impl TypedData for ProcBody { }
+
+/// Checks if a block was passed to the current method.
+pub fn block_given() -> bool {
+    unsafe { ffi::rb_block_given_p() != 0 }
+}
+
+/// Yields values to the current method's block.
+///
+/// If the block uses `break`, this returns `ErrorKind::Jump`, which
+/// must be returned from the method so Ruby can finish unwinding.
+pub fn yield_value(args: &[Value]) -> Result<Value, ErrorKind> {
+    if !block_given() {
+        return Err(RubyException::new(classes::LocalJumpError(), "no block given (yield)").into());
+    }
+
+    VM::protect(|| Value::from(unsafe {
+        ffi::rb_yield_values2(args.len() as libc::c_int, args.as_ptr() as *const _)
+    }))
+}
+
+/// Gets the current method's block as a `Proc`.
+///
+/// Returns `None` if no block was given.
+pub fn block_proc() -> Option<Value> {
+    if block_given() {
+        Some(Value::from(unsafe { ffi::rb_block_proc() }))
+    } else {
+        None
+    }
+}
+
+/// Calls a method, passing a Rust closure as its block.
+///
+/// The closure is given the values yielded to the block, and its
+/// result is the value of the `yield`.
+pub fn call_with_block<F>(receiver: Value, method_name: &str, args: &[Value], f: F)
+    -> Result<Value, ErrorKind>
+    where F: FnMut(&[Value]) -> Result<Value, ErrorKind> {
+    extern "C" fn block<F>(_yielded: ffi::VALUE, data: ffi::VALUE, argc: libc::c_int,
+                           argv: *const ffi::VALUE, _block: ffi::VALUE) -> ffi::VALUE
+        where F: FnMut(&[Value]) -> Result<Value, ErrorKind> {
+        let result = callback::call(|| {
+            let f = unsafe { &*(data.0 as *const RefCell<F>) };
+            let mut f = f.try_borrow_mut()
+                .map_err(|_| ErrorKind::VM("a Rust block cannot be called recursively".to_owned()))?;
+
+            (*f)(arguments(argc, argv))
+        });
+
+        match result {
+            Ok(value) => value.0,
+            Err(e) => callback::raise(e),
+        }
+    }
+
+    let f = RefCell::new(f);
+    let id = Value::intern(method_name);
+
+    VM::protect(|| Value::from(unsafe {
+        ffi::rb_block_call(receiver.0, id, args.len() as libc::c_int, args.as_ptr() as *const _,
+                           block::<F>, ffi::VALUE(&f as *const _ as ffi::INNER_VALUE))
+    }))
+}
+
+/// Creates a `Proc` that calls a Rust closure.
+///
+/// With an arity, the proc is a lambda and Ruby checks the number of
+/// arguments it is called with. A negative arity accepts any number.
+pub fn new_proc(f: Box<ProcFn>, arity: Option<i32>) -> Value {
+    let body = typed_data::wrap(classes::Object(), ProcBody(f));
+
+    Value::from(unsafe {
+        match arity {
+            None => ffi::rb_proc_new(proc_body, body.0),
+            Some(arity) if arity < 0 => ffi::rb_func_lambda_new(proc_body, body.0, 0, -1),
+            Some(arity) => ffi::rb_func_lambda_new(proc_body, body.0, arity, arity),
+        }
+    })
+}
+
+/// The entry point for all closure-based procs.
+extern "C" fn proc_body(_yielded: ffi::VALUE, body: ffi::VALUE, argc: libc::c_int,
+                        argv: *const ffi::VALUE, _block: ffi::VALUE) -> ffi::VALUE {
+    let result = callback::call(|| {
+        let body = Value::from(body);
+        let body = body.downcast_ref::<ProcBody>()
+            .ok_or_else(|| ErrorKind::VM("proc has no Rust closure".to_owned()))?;
+
+        (body.0)(arguments(argc, argv))
+    });
+
+    match result {
+        Ok(value) => value.0,
+        Err(e) => callback::raise(e),
+    }
+}
+
+fn arguments<'a>(argc: libc::c_int, argv: *const ffi::VALUE) -> &'a [Value] {
+    if argc == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(argv as *const Value, argc as usize) }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{classes, ErrorKind, Value};
+use super::Class;
+
+/// An exception class builder.
+///
+/// Exception classes extend `StandardError` unless told otherwise.
+pub struct ExceptionClass
+{
+    class: Class,
+}
+
+impl ExceptionClass
+{
+    /// Creates a new exception class under the global scope.
+    pub fn new<S>(name: S) -> Self where S: Into<String> {
+        Self::new_under(name, None)
+    }
+
+    /// Creates a new exception class under a value (module, class, etc).
+    pub fn new_under<S>(name: S, parent: Option<Value>) -> Self where S: Into<String> {
+        ExceptionClass {
+            class: Class::new_under(name, parent).extend(classes::StandardError()),
+        }
+    }
+
+    /// Creates the class under a value.
+    pub fn under(mut self, parent: Value) -> Self {
+        self.class = self.class.under(parent);
+        self
+    }
+
+    /// Sets the base class.
+    ///
+    /// This should be `Exception` or one of its subclasses.
+    pub fn extend(mut self, base_class: Value) -> Self {
+        self.class = self.class.extend(base_class);
+        self
+    }
+
+    /// Adds a field.
+    ///
+    /// Fields are stored in instance variables of the same name
+    /// and are exposed to Ruby with a reader method.
+    pub fn field<S>(mut self, name: S) -> Self where S: Into<String> {
+        self.class = self.class.attr_reader(name);
+        self
+    }
+
+    /// Customises the underlying class builder, for example to add methods.
+    pub fn with_class<F>(mut self, f: F) -> Self where F: FnOnce(Class) -> Class {
+        self.class = f(self.class);
+        self
+    }
+
+    /// Builds the exception class.
+    ///
+    /// Panics under the same conditions as `Class::build`.
+    pub fn build(self) -> Value {
+        self.class.build()
+    }
+
+    /// Builds the exception class, returning an error instead of panicking.
+    ///
+    /// See `Class::try_build`.
+    pub fn try_build(self) -> Result<Value, ErrorKind> {
+        self.class.try_build()
+    }
+}
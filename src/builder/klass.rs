@@ -1,12 +1,55 @@
-use crate::{ffi, libc, util, Value};
+use crate::{callback, ffi, libc, typed_data, util, ArgSpec, Args, ErrorKind, IntoValue, RootedValue, TryFromValue, TypedData, Value, VM};
 
 struct Method {
     name: String,
-    func: *mut extern fn() -> Value,
-    arg_count: i8,
+    body: MethodBody,
+    visibility: Visibility,
 }
 
-struct Constant(String, Value);
+/// The visibility of a method.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Protected,
+    Private,
+}
+
+impl Visibility {
+    /// The `Module` method that sets the visibility.
+    fn setter(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Protected => "protected",
+            Visibility::Private => "private",
+        }
+    }
+
+    /// Defines a method with this visibility.
+    pub(crate) fn define_method(self, class: Value, name: &str, func: *mut extern "C" fn() -> ffi::VALUE,
+                                arg_count: libc::c_int) {
+        let name = util::c_string(name);
+
+        unsafe {
+            match self {
+                Visibility::Public => { ffi::rb_define_method(class.0, name.as_ptr(), func, arg_count); },
+                Visibility::Protected => ffi::rb_define_protected_method(class.0, name.as_ptr(), func, arg_count),
+                Visibility::Private => ffi::rb_define_private_method(class.0, name.as_ptr(), func, arg_count),
+            }
+        }
+    }
+}
+
+enum MethodBody {
+    /// A C function with a fixed arity.
+    Raw {
+        func: *mut extern fn() -> Value,
+        arg_count: i8,
+    },
+    /// A Rust closure.
+    Closure(Box<callback::MethodFn>),
+}
+
+struct Constant(String, RootedValue);
 
 struct Alias {
     new_name: String,
@@ -23,17 +66,21 @@ struct Accessor {
 pub struct Class
 {
     name: String,
-    base_class: Value,
-    parent: Option<Value>,
+    base_class: RootedValue,
+    parent: Option<RootedValue>,
+    allocator: Option<extern "C" fn(ffi::VALUE) -> ffi::VALUE>,
 
     methods: Vec<Method>,
     singleton_methods: Vec<Method>,
 
-    included_modules: Vec<Value>,
-    prepended_modules: Vec<Value>,
+    included_modules: Vec<RootedValue>,
+    prepended_modules: Vec<RootedValue>,
     constants: Vec<Constant>,
     aliases: Vec<Alias>,
     accessors: Vec<Accessor>,
+    visibilities: Vec<(String, Visibility)>,
+    undefined_methods: Vec<String>,
+    removed_methods: Vec<String>,
 }
 
 impl Class {
@@ -44,8 +91,9 @@ impl Class {
     pub fn new_under<S>(name: S, parent: Option<Value>) -> Self where S: Into<String> {
         Class {
             name: name.into(),
-            base_class: unsafe { ffi::rb_cObject.into() },
-            parent: parent,
+            base_class: RootedValue::new(unsafe { ffi::rb_cObject.into() }),
+            parent: parent.map(RootedValue::new),
+            allocator: None,
 
             methods: Vec::new(),
             singleton_methods: Vec::new(),
@@ -55,43 +103,130 @@ impl Class {
             constants: Vec::new(),
             aliases: Vec::new(),
             accessors: Vec::new(),
+            visibilities: Vec::new(),
+            undefined_methods: Vec::new(),
+            removed_methods: Vec::new(),
         }
     }
 
     /// Creates the class under a value.
     /// This may be a class, a module, etc.
     pub fn under(mut self, parent: Value) -> Self {
-        self.parent = Some(parent);
+        self.parent = Some(parent.root());
         self
     }
 
     /// Sets the base class.
     pub fn extend(mut self, base_class: Value) -> Self {
-        self.base_class = base_class;
+        self.base_class = base_class.root();
+        self
+    }
+
+    /// Makes instances of the class wrap a Rust value.
+    ///
+    /// Newly allocated instances are empty until given a value with
+    /// `typed_data::set`, usually from `initialize`. Use
+    /// `typed_data::wrap` to create an instance with a value directly.
+    pub fn typed_data<T>(mut self) -> Self where T: TypedData {
+        self.allocator = Some(typed_data::allocate::<T>);
         self
     }
 
     /// Includes a module.
     pub fn include(mut self, module: Value) -> Self {
-        self.included_modules.push(module);
+        self.included_modules.push(module.root());
         self
     }
 
     /// Prepends a module.
     pub fn prepend(mut self, module: Value) -> Self {
-        self.prepended_modules.push(module);
+        self.prepended_modules.push(module.root());
         self
     }
 
     /// Defines a method.
-    pub fn method<S>(mut self, name: S, func_addr: *mut extern fn() -> Value, arg_count: i8) -> Self
+    ///
+    /// A non-negative `arg_count` is the number of arguments, passed to the
+    /// function after the receiver. An `arg_count` of -1 passes
+    /// `(argc: c_int, argv: *const Value, receiver: Value)` for any number
+    /// of arguments, and -2 passes `(receiver: Value, args: Value)` with
+    /// the arguments in an `Array`.
+    pub fn method<S>(self, name: S, func_addr: *mut extern fn() -> Value, arg_count: i8) -> Self
         where S: Into<String> {
-        self.methods.push(Method {
-            name: name.into(),
-            func: func_addr,
-            arg_count: arg_count,
-        });
-        self
+        self.raw_method(name, func_addr, arg_count, Visibility::Public)
+    }
+
+    /// Defines a private method.
+    ///
+    /// `arg_count` follows the same conventions as `method`.
+    pub fn private_method<S>(self, name: S, func_addr: *mut extern "C" fn() -> Value, arg_count: i8) -> Self
+        where S: Into<String> {
+        self.raw_method(name, func_addr, arg_count, Visibility::Private)
+    }
+
+    /// Defines a protected method.
+    ///
+    /// `arg_count` follows the same conventions as `method`.
+    pub fn protected_method<S>(self, name: S, func_addr: *mut extern "C" fn() -> Value, arg_count: i8) -> Self
+        where S: Into<String> {
+        self.raw_method(name, func_addr, arg_count, Visibility::Protected)
+    }
+
+    /// Defines a method implemented by a Rust closure.
+    ///
+    /// The closure is given the receiver and the arguments, and
+    /// can take any number of arguments. Returning an error raises it
+    /// as a Ruby exception, as does panicking.
+    pub fn method_fn<S, F>(self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.closure_method(name, f, Visibility::Public)
+    }
+
+    /// Defines a private method implemented by a Rust closure.
+    pub fn private_method_fn<S, F>(self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.closure_method(name, f, Visibility::Private)
+    }
+
+    /// Defines a protected method implemented by a Rust closure.
+    pub fn protected_method_fn<S, F>(self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.closure_method(name, f, Visibility::Protected)
+    }
+
+    /// Defines a method implemented by a Rust closure taking the arguments in a spec.
+    ///
+    /// The arguments are checked against the spec before the closure runs,
+    /// raising `ArgumentError` if they do not match.
+    pub fn method_with_args<S, F>(self, name: S, spec: ArgSpec, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &Args) -> Result<Value, ErrorKind> + 'static {
+        self.method_fn(name, move |receiver, args| f(receiver, &spec.parse(args)?))
+    }
+
+    /// Defines a method that runs a Rust closure without holding the Global VM Lock.
+    ///
+    /// Lets CPU-heavy Rust code run in parallel with Ruby threads. The
+    /// arguments are converted into a tuple of Rust values before releasing
+    /// the lock, and the result converted back once it is reacquired.
+    ///
+    /// # Safety
+    ///
+    /// The closure runs without the lock, so it must not call into Ruby or
+    /// touch any Ruby values, including any taken as arguments or captured
+    /// by the closure. See `VM::without_gvl`.
+    pub unsafe fn method_fn_without_gvl<S, F, A, R>(self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(A) -> Result<R, ErrorKind> + 'static,
+              A: TryFromValue,
+              R: IntoValue {
+        self.method_fn(name, move |_, args| {
+            let args = A::try_from_value(args.into_value())?;
+            VM::without_gvl(|| f(args))?.map(IntoValue::into_value)
+        })
     }
 
     /// Defines a singleton method.
@@ -99,16 +234,37 @@ impl Class {
         where S: Into<String> {
         self.singleton_methods.push(Method {
             name: name.into(),
-            func: func_addr,
-            arg_count: arg_count,
+            body: MethodBody::Raw { func: func_addr, arg_count: arg_count },
+            visibility: Visibility::Public,
         });
         self
     }
 
+    /// Defines a singleton method implemented by a Rust closure.
+    ///
+    /// The receiver passed to the closure is the class itself.
+    pub fn singleton_method_fn<S, F>(mut self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.singleton_methods.push(Method {
+            name: name.into(),
+            body: MethodBody::Closure(Box::new(f)),
+            visibility: Visibility::Public,
+        });
+        self
+    }
+
+    /// Defines a singleton method implemented by a Rust closure taking the arguments in a spec.
+    pub fn singleton_method_with_args<S, F>(self, name: S, spec: ArgSpec, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &Args) -> Result<Value, ErrorKind> + 'static {
+        self.singleton_method_fn(name, move |receiver, args| f(receiver, &spec.parse(args)?))
+    }
+
     /// Defines a constant.
     pub fn constant<S>(mut self, name: S, value: Value) -> Self
         where S: Into<String> {
-        self.constants.push(Constant(name.into(), value));
+        self.constants.push(Constant(name.into(), value.root()));
         self
     }
 
@@ -140,18 +296,63 @@ impl Class {
         self.define_accessor(name, false, true)
     }
 
+    /// Sets the visibility of a method.
+    ///
+    /// This applies once every method, alias and accessor has been defined,
+    /// so it can be used to hide an accessor. Writers are named with a
+    /// trailing `=`.
+    pub fn visibility<S>(mut self, name: S, visibility: Visibility) -> Self
+        where S: Into<String> {
+        self.visibilities.push((name.into(), visibility));
+        self
+    }
+
+    /// Undefines a method, so that instances do not respond to it
+    /// even if an ancestor defines it.
+    pub fn undef_method<S>(mut self, name: S) -> Self
+        where S: Into<String> {
+        self.undefined_methods.push(name.into());
+        self
+    }
+
+    /// Removes a method defined in the class itself, so that calls
+    /// fall through to the ancestors.
+    pub fn remove_method<S>(mut self, name: S) -> Self
+        where S: Into<String> {
+        self.removed_methods.push(name.into());
+        self
+    }
+
     /// Builds the class.
+    ///
+    /// Panics if a method given to `visibility`, `undef_method` or
+    /// `remove_method` is not defined. Use `try_build` to handle this.
     pub fn build(self) -> Value {
+        match self.try_build() {
+            Ok(value) => value,
+            Err(e) => panic!("failed to build class: {}", e),
+        }
+    }
+
+    /// Builds the class, returning an error if a method given to
+    /// `visibility`, `undef_method` or `remove_method` is not defined.
+    ///
+    /// The class and any methods defined before the error remain defined.
+    pub fn try_build(self) -> Result<Value, ErrorKind> {
         let name = util::c_string(&self.name);
 
         let value = Value::from(unsafe {
-            if let Some(parent) = self.parent {
+            if let Some(ref parent) = self.parent {
                 ffi::rb_define_class_under(parent.0, name.as_ptr(), self.base_class.0)
             } else {
                 ffi::rb_define_class(name.as_ptr(), self.base_class.0)
             }
         });
 
+        if let Some(allocator) = self.allocator {
+            unsafe { ffi::rb_define_alloc_func(value.0, allocator) };
+        }
+
         for method in self.methods {
             Self::define_method(false, value, method);
         }
@@ -199,7 +400,45 @@ impl Class {
             }
         }
 
-        value
+        // These raise `NameError` for unknown methods.
+        for (name, visibility) in self.visibilities {
+            let args = [Value::symbol(name)];
+            VM::protect(|| value.send(visibility.setter(), &args))?;
+        }
+
+        for name in self.undefined_methods {
+            let name = util::c_string(&name);
+            VM::protect(|| unsafe { ffi::rb_undef_method(value.0, name.as_ptr()) })?;
+        }
+
+        for name in self.removed_methods {
+            let name = util::c_string(&name);
+            VM::protect(|| unsafe { ffi::rb_remove_method(value.0, name.as_ptr()) })?;
+        }
+
+        Ok(value)
+    }
+
+    fn raw_method<S>(mut self, name: S, func_addr: *mut extern "C" fn() -> Value, arg_count: i8,
+                     visibility: Visibility) -> Self
+        where S: Into<String> {
+        self.methods.push(Method {
+            name: name.into(),
+            body: MethodBody::Raw { func: func_addr, arg_count },
+            visibility,
+        });
+        self
+    }
+
+    fn closure_method<S, F>(mut self, name: S, f: F, visibility: Visibility) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.methods.push(Method {
+            name: name.into(),
+            body: MethodBody::Closure(Box::new(f)),
+            visibility,
+        });
+        self
     }
 
     fn define_accessor<S>(mut self, name: S, reader: bool, writer: bool) -> Self
@@ -213,15 +452,27 @@ impl Class {
     }
 
     fn define_method(is_singleton: bool, value: Value, method: Method) {
-        let f = if is_singleton {
-            ffi::rb_define_module_function
-        } else {
-            ffi::rb_define_method
-        };
-
-        unsafe {
-            f(value.0, util::c_string(&method.name).as_ptr(),
-              method.func as *mut _, method.arg_count as libc::c_int);
+        match method.body {
+            MethodBody::Raw { func, arg_count } => {
+                if is_singleton {
+                    unsafe {
+                        ffi::rb_define_module_function(value.0, util::c_string(&method.name).as_ptr(),
+                                                       func as *mut _, arg_count as libc::c_int);
+                    }
+                } else {
+                    method.visibility.define_method(value, &method.name, func as *mut _,
+                                                    arg_count as libc::c_int);
+                }
+            },
+            MethodBody::Closure(f) => {
+                let owner = if is_singleton {
+                    Value::from(unsafe { ffi::rb_singleton_class(value.0) })
+                } else {
+                    value
+                };
+
+                callback::define_method(owner, &method.name, f, method.visibility);
+            },
         }
     }
 }
@@ -1,5 +1,7 @@
-pub use self::klass::Class;
+pub use self::exception::ExceptionClass;
+pub use self::klass::{Class, Visibility};
 pub use self::module::Module;
 
+pub mod exception;
 pub mod klass;
 pub mod module;
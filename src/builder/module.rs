@@ -1,22 +1,31 @@
-use crate::{ffi, libc, util, Value};
+use crate::{callback, ffi, libc, util, ArgSpec, Args, ErrorKind, RootedValue, Value};
 
 struct Function {
     name: String,
-    func: *mut extern fn() -> Value,
-    arg_count: i8,
+    body: FunctionBody,
 }
 
-struct Constant(String, Value);
+enum FunctionBody {
+    /// A C function with a fixed arity.
+    Raw {
+        func: *mut extern fn() -> Value,
+        arg_count: i8,
+    },
+    /// A Rust closure.
+    Closure(Box<callback::MethodFn>),
+}
+
+struct Constant(String, RootedValue);
 
 /// A module builder.
 pub struct Module
 {
     name: String,
-    parent: Option<Value>,
+    parent: Option<RootedValue>,
 
     constants: Vec<Constant>,
-    included_modules: Vec<Value>,
-    prepended_modules: Vec<Value>,
+    included_modules: Vec<RootedValue>,
+    prepended_modules: Vec<RootedValue>,
 
     functions: Vec<Function>,
 }
@@ -32,7 +41,7 @@ impl Module
     pub fn new_under<S>(name: S, parent: Option<Value>) -> Self where S: Into<String> {
         Module {
             name: name.into(),
-            parent: parent,
+            parent: parent.map(RootedValue::new),
 
             constants: Vec::new(),
             included_modules: Vec::new(),
@@ -45,38 +54,62 @@ impl Module
     /// Adds a constant to the module.
     pub fn constant<S>(mut self, name: S, value: Value) -> Self
         where S: Into<String> {
-        self.constants.push(Constant(name.into(), value));
+        self.constants.push(Constant(name.into(), value.root()));
         self
     }
 
     /// Includes a module.
     pub fn include(mut self, module: Value) -> Self {
-        self.included_modules.push(module);
+        self.included_modules.push(module.root());
         self
     }
 
     /// Prepends a module.
     pub fn prepend(mut self, module: Value) -> Self {
-        self.prepended_modules.push(module);
+        self.prepended_modules.push(module.root());
         self
     }
 
     /// Defines a function.
+    ///
+    /// `arg_count` follows the same conventions as `Class::method`.
     pub fn function<S>(mut self, name: S, func_addr: *mut extern fn() -> Value, arg_count: i8) -> Self
         where S: Into<String> {
         self.functions.push(Function {
             name: name.into(),
-            func: func_addr,
-            arg_count: arg_count,
+            body: FunctionBody::Raw { func: func_addr, arg_count: arg_count },
         });
         self
     }
 
+    /// Defines a function implemented by a Rust closure.
+    ///
+    /// Panics inside the closure are raised as Ruby exceptions.
+    pub fn function_fn<S, F>(mut self, name: S, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        self.functions.push(Function {
+            name: name.into(),
+            body: FunctionBody::Closure(Box::new(f)),
+        });
+        self
+    }
+
+    /// Defines a function implemented by a Rust closure taking the arguments in a spec.
+    ///
+    /// The arguments are checked against the spec before the closure runs,
+    /// raising `ArgumentError` if they do not match.
+    pub fn function_with_args<S, F>(self, name: S, spec: ArgSpec, f: F) -> Self
+        where S: Into<String>,
+              F: Fn(Value, &Args) -> Result<Value, ErrorKind> + 'static {
+        self.function_fn(name, move |receiver, args| f(receiver, &spec.parse(args)?))
+    }
+
     pub fn build(self) -> Value {
         let name = util::c_string(&self.name);
 
         let value = Value::from(unsafe {
-            if let Some(parent) = self.parent {
+            if let Some(ref parent) = self.parent {
                 ffi::rb_define_module_under(parent.0, name.as_ptr())
             } else {
                 ffi::rb_define_module(name.as_ptr())
@@ -94,13 +127,18 @@ impl Module
         }
 
         for function in self.functions {
-            unsafe {
-                ffi::rb_define_module_function(
-                    value.0,
-                    util::c_string(&function.name).as_ptr(),
-                    function.func as *mut _,
-                    function.arg_count as libc::c_int,
-                );
+            match function.body {
+                FunctionBody::Raw { func, arg_count } => unsafe {
+                    ffi::rb_define_module_function(
+                        value.0,
+                        util::c_string(&function.name).as_ptr(),
+                        func as *mut _,
+                        arg_count as libc::c_int,
+                    );
+                },
+                FunctionBody::Closure(f) => {
+                    callback::define_module_function(value, &function.name, f);
+                },
             }
         }
 
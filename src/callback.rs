@@ -0,0 +1,192 @@
+//! Dispatching of Ruby method calls to Rust closures.
+//!
+//! Every closure-based method is defined with the same C entry point.
+//! The closures are kept in a table stored in a hidden instance variable
+//! of the class that owns them. When Ruby calls the entry point, it looks
+//! up the method's name in its owner's table and forwards the call to the
+//! closure stored there. Ruby copies instance variables along with methods
+//! when a class is duplicated, so copies share the table with the original.
+//!
+//! Neither Rust panics nor Ruby exceptions may cross the boundary between
+//! the two. Panics are caught and turned into Ruby exceptions, and errors
+//! are only raised once every Rust value on the stack has been dropped.
+
+use crate::builder::Visibility;
+use crate::{classes, ffi, gc, libc, typed_data, util, ErrorKind, RubyException, TypedData, Value};
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{mem, slice};
+
+/// A Rust closure callable as a Ruby method.
+///
+/// Takes the receiver and the arguments.
+pub type MethodFn = dyn Fn(Value, &[Value]) -> Result<Value, ErrorKind>;
+
+struct Method(Box<MethodFn>);
+
+/// The methods of a class, keyed by their name symbol.
+///
+/// Not prefixed with `@`, so it is invisible to Ruby code.
+const METHODS_IVAR: &str = "__rurust_methods__";
+
+#[derive(Default)]
+struct MethodTable(HashMap<ffi::INNER_VALUE, Rc<Method>>);
+
+impl TypedData for MethodTable { }
+
+/// The class of exception raised when a callback panics.
+///
+/// Zero means `RuntimeError`.
+static PANIC_CLASS: AtomicUsize = AtomicUsize::new(0);
+
+/// Defines a method on a class or module that dispatches to a closure.
+pub fn define_method(owner: Value, name: &str, f: Box<MethodFn>, visibility: Visibility) {
+    register(owner, name, Rc::new(Method(f)));
+    visibility.define_method(owner, name, trampoline as *mut _, -1);
+}
+
+/// Defines a module function that dispatches to a closure.
+///
+/// Like `rb_define_module_function`, this defines both a singleton method
+/// and a private instance method.
+pub fn define_module_function(module: Value, name: &str, f: Box<MethodFn>) {
+    let method = Rc::new(Method(f));
+    let singleton_class = Value::from(unsafe { ffi::rb_singleton_class(module.0) });
+
+    register(module, name, method.clone());
+    register(singleton_class, name, method);
+
+    unsafe {
+        ffi::rb_define_module_function(module.0, util::c_string(name).as_ptr(),
+                                       trampoline as *mut _, -1);
+    }
+}
+
+/// Sets the class of exception raised when a callback panics.
+///
+/// The class is kept alive for the rest of the VM's life, as it may be
+/// anonymous or later removed from its constant.
+pub fn set_panic_class(class: Value) {
+    gc::register_permanently(class);
+    PANIC_CLASS.store(class.0 .0, Ordering::SeqCst);
+}
+
+/// Calls a Rust function from Ruby, catching any panics.
+///
+/// The result must be passed back to Ruby, raising if it is an error.
+pub fn call<F>(f: F) -> Result<Value, ErrorKind>
+    where F: FnOnce() -> Result<Value, ErrorKind> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => Err(ErrorKind::Exception(panic_exception(payload))),
+    }
+}
+
+fn register(owner: Value, name: &str, method: Rc<Method>) {
+    let key = unsafe { ffi::rb_id2sym(Value::intern(name)) }.0;
+
+    let table = match method_table(owner) {
+        Some(table) => table,
+        None => {
+            let table = typed_data::wrap(classes::Object(), MethodTable::default());
+            unsafe { ffi::rb_ivar_set(owner.0, Value::intern(METHODS_IVAR), table.0) };
+            table
+        },
+    };
+
+    table.downcast_mut::<MethodTable>().expect("method table is borrowed").0.insert(key, method);
+}
+
+/// Gets the table of closures stored on a class, if it has one.
+fn method_table(owner: Value) -> Option<Value> {
+    let table = Value::from(unsafe { ffi::rb_attr_get(owner.0, Value::intern(METHODS_IVAR)) });
+
+    if typed_data::is_wrapping::<MethodTable>(table) {
+        Some(table)
+    } else {
+        None
+    }
+}
+
+/// Converts an error into an exception object.
+///
+/// Consumes the error so that nothing needs dropping once we raise.
+pub fn exception_for(error: ErrorKind) -> RubyException {
+    match error {
+        ErrorKind::Exception(exception) => exception,
+        ErrorKind::VM(message) => RubyException::new(crate::classes::RuntimeError(), message),
+        ErrorKind::Conversion(e) => RubyException::new(crate::classes::TypeError(), e.to_string()),
+        ErrorKind::Jump(..) => RubyException::new(crate::classes::RuntimeError(),
+                                                  "non-local jump cannot be converted into an exception"),
+    }
+}
+
+/// Creates an exception object from a panic.
+fn panic_exception(payload: Box<dyn Any + Send>) -> RubyException {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Rust panic"
+    };
+
+    let class = match PANIC_CLASS.load(Ordering::SeqCst) {
+        0 => classes::RuntimeError(),
+        class => Value::from(ffi::VALUE(class)),
+    };
+
+    // A custom class's `initialize` may raise, which must not unwind over us.
+    RubyException::try_new(class, message)
+        .unwrap_or_else(|_| RubyException::new(classes::RuntimeError(), message))
+}
+
+/// Raises an error as a Ruby exception.
+///
+/// This longjmps, so the caller must not have any live values
+/// with destructors on its stack.
+pub fn raise(error: ErrorKind) -> ! {
+    match error {
+        ErrorKind::Jump(tag) => unsafe { ffi::rb_jump_tag(tag) },
+        error => exception_for(error).raise(),
+    }
+}
+
+/// Calls the closure registered for the currently executing method.
+fn dispatch(argc: libc::c_int, argv: *const Value, receiver: Value) -> Result<Value, ErrorKind> {
+    let (id, owner) = unsafe {
+        let mut id = mem::MaybeUninit::uninit();
+        let mut owner = ffi::Qnil;
+        ffi::rb_frame_method_id_and_class(id.as_mut_ptr(), &mut owner);
+        (id.assume_init(), owner)
+    };
+    let key = unsafe { ffi::rb_id2sym(id) }.0;
+
+    // Don't borrow the table during the call, the method may define more methods.
+    let method = method_table(Value::from(owner))
+        .and_then(|table| table.downcast_ref::<MethodTable>()?.0.get(&key).cloned());
+    let method = match method {
+        Some(method) => method,
+        None => return Err(ErrorKind::VM("no Rust method is registered for this call".to_owned())),
+    };
+
+    let args = if argc == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(argv, argc as usize) }
+    };
+
+    (method.0)(receiver, args)
+}
+
+/// The entry point for all closure-based methods.
+extern "C" fn trampoline(argc: libc::c_int, argv: *const Value, receiver: Value) -> Value {
+    match call(|| dispatch(argc, argv, receiver)) {
+        Ok(value) => value,
+        Err(e) => raise(e),
+    }
+}
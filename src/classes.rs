@@ -1,6 +1,6 @@
 //! Accesses for standard Ruby classes.
 
-use crate::{ffi, Value};
+use crate::{ffi, ErrorKind, Value};
 
 // Ruby classes do not follow the same conventions.
 
@@ -8,11 +8,16 @@ macro_rules! define_class {
     ( $class_name:ident => $ffi_value:expr ) => {
         #[allow(non_snake_case)]
         pub fn $class_name() -> Value {
-            Value(unsafe { $ffi_value })
+            Value::from(unsafe { $ffi_value })
         }
     }
 }
 
+/// Looks up a class or module by its full path, such as `MyLib::Error`.
+pub fn find(path: &str) -> Result<Value, ErrorKind> {
+    Object().try_send("const_get", &[Value::string(path)])
+}
+
 define_class!(Array => ffi::rb_cArray);
 define_class!(BasicObject => ffi::rb_cBasicObject);
 define_class!(Binding => ffi::rb_cBinding);
@@ -0,0 +1,343 @@
+//! Conversions between Rust and Ruby values.
+
+use crate::{ffi, libc, RArray, RHash, RString, Value};
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::fmt;
+
+/// A type that can be converted into a Ruby value.
+pub trait IntoValue {
+    /// Converts into a Ruby value.
+    fn into_value(self) -> Value;
+}
+
+/// A type that can be extracted out of a Ruby value.
+pub trait TryFromValue: Sized {
+    /// Attempts to convert a Ruby value into this type.
+    fn try_from_value(value: Value) -> Result<Self, ConversionError>;
+}
+
+/// An error converting a Ruby value into a Rust value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The Ruby value was of the wrong type.
+    WrongType {
+        /// The name of the Ruby type that was expected.
+        expected: &'static str,
+        /// The class name of the value we got.
+        found: String,
+    },
+    /// The value does not fit into the Rust type.
+    OutOfRange {
+        /// The name of the Rust type.
+        target: &'static str,
+    },
+    /// An array had the wrong number of elements.
+    WrongLength {
+        /// The number of elements expected.
+        expected: usize,
+        /// The number of elements in the array.
+        found: usize,
+    },
+    /// A conversion defined outside of this crate failed, such as
+    /// deserializing with serde.
+    Custom(String),
+    /// The Rust value wrapped by an object was already borrowed, for
+    /// example by a method that is still running.
+    AlreadyBorrowed,
+}
+
+impl ConversionError {
+    /// Creates an error for a value of an unexpected type.
+    pub fn wrong_type(expected: &'static str, value: Value) -> Self {
+        ConversionError::WrongType { expected, found: value.class_name() }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConversionError::WrongType { expected, ref found } => {
+                write!(fmt, "no implicit conversion of {} into {}", found, expected)
+            },
+            ConversionError::OutOfRange { target } => {
+                write!(fmt, "integer is out of range for {}", target)
+            },
+            ConversionError::WrongLength { expected, found } => {
+                write!(fmt, "expected an array of length {}, got {}", expected, found)
+            },
+            ConversionError::Custom(ref message) => write!(fmt, "{}", message),
+            ConversionError::AlreadyBorrowed => write!(fmt, "object is already borrowed"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError { }
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value { self }
+}
+
+impl TryFromValue for Value {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> { Ok(value) }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value { Value::nil() }
+}
+
+impl TryFromValue for () {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        if value.is_nil() { Ok(()) } else { Err(ConversionError::wrong_type("NilClass", value)) }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value { Value::boolean(self) }
+}
+
+impl TryFromValue for bool {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        if value.is_true() {
+            Ok(true)
+        } else if value.is_false() {
+            Ok(false)
+        } else {
+            Err(ConversionError::wrong_type("true or false", value))
+        }
+    }
+}
+
+macro_rules! impl_signed_integer {
+    ( $( $ty:ty ),* ) => { $(
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                Value::from(unsafe { ffi::rb_ll2inum(self as libc::c_longlong) })
+            }
+        }
+
+        impl TryFromValue for $ty {
+            fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+                <$ty>::try_from(i128::try_from_value(value)?)
+                    .map_err(|_| ConversionError::OutOfRange { target: stringify!($ty) })
+            }
+        }
+    )* }
+}
+
+macro_rules! impl_unsigned_integer {
+    ( $( $ty:ty ),* ) => { $(
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                Value::from(unsafe { ffi::rb_ull2inum(self as libc::c_ulonglong) })
+            }
+        }
+
+        impl TryFromValue for $ty {
+            fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+                <$ty>::try_from(u128::try_from_value(value)?)
+                    .map_err(|_| ConversionError::OutOfRange { target: stringify!($ty) })
+            }
+        }
+    )* }
+}
+
+impl_signed_integer!(i8, i16, i32, i64, isize);
+impl_unsigned_integer!(u8, u16, u32, u64, usize);
+
+impl IntoValue for i128 {
+    fn into_value(self) -> Value {
+        Value::from(unsafe {
+            ffi::rb_integer_unpack(&self as *const i128 as *const libc::c_void, 1, 16, 0,
+                                   INTEGER_PACK_FLAGS | ffi::INTEGER_PACK_2COMP)
+        })
+    }
+}
+
+impl TryFromValue for i128 {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        let (negative, magnitude) = integer_parts(value, "i128")?;
+
+        if negative && magnitude <= i128::MIN.unsigned_abs() {
+            Ok((magnitude as i128).wrapping_neg())
+        } else if !negative && magnitude <= i128::MAX as u128 {
+            Ok(magnitude as i128)
+        } else {
+            Err(ConversionError::OutOfRange { target: "i128" })
+        }
+    }
+}
+
+impl IntoValue for u128 {
+    fn into_value(self) -> Value {
+        Value::from(unsafe {
+            ffi::rb_integer_unpack(&self as *const u128 as *const libc::c_void, 1, 16, 0, INTEGER_PACK_FLAGS)
+        })
+    }
+}
+
+impl TryFromValue for u128 {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        match integer_parts(value, "u128")? {
+            (true, _) => Err(ConversionError::OutOfRange { target: "u128" }),
+            (false, magnitude) => Ok(magnitude),
+        }
+    }
+}
+
+macro_rules! impl_float {
+    ( $( $ty:ty ),* ) => { $(
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value { Value::float(self) }
+        }
+
+        impl TryFromValue for $ty {
+            fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+                if value.is_float() || value.is_integer() {
+                    Ok(value.to_f64() as $ty)
+                } else {
+                    Err(ConversionError::wrong_type("Float", value))
+                }
+            }
+        }
+    )* }
+}
+
+impl_float!(f32, f64);
+
+impl IntoValue for String {
+    fn into_value(self) -> Value { Value::string(self) }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value { Value::string(self) }
+}
+
+impl TryFromValue for String {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RString::try_from_value(value)?.try_to_string()
+            .ok_or_else(|| ConversionError::wrong_type("a UTF-8 String", value))
+    }
+}
+
+impl<T> IntoValue for Option<T> where T: IntoValue {
+    fn into_value(self) -> Value {
+        match self {
+            Some(v) => v.into_value(),
+            None => Value::nil(),
+        }
+    }
+}
+
+impl<T> TryFromValue for Option<T> where T: TryFromValue {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        if value.is_nil() { Ok(None) } else { T::try_from_value(value).map(Some) }
+    }
+}
+
+impl<T> IntoValue for Vec<T> where T: IntoValue {
+    fn into_value(self) -> Value {
+        RArray::from(self).value()
+    }
+}
+
+impl<T> IntoValue for &[T] where T: IntoValue + Clone {
+    fn into_value(self) -> Value {
+        RArray::from(self).value()
+    }
+}
+
+impl<T> TryFromValue for Vec<T> where T: TryFromValue {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RArray::try_from_value(value)?.try_to_vec()
+    }
+}
+
+impl<K, V> IntoValue for HashMap<K, V> where K: IntoValue, V: IntoValue {
+    fn into_value(self) -> Value {
+        RHash::from(self).value()
+    }
+}
+
+impl<K, V> TryFromValue for HashMap<K, V>
+    where K: TryFromValue + Eq + Hash, V: TryFromValue {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RHash::try_from_value(value)?.try_to_hash_map()
+    }
+}
+
+impl<K, V> IntoValue for BTreeMap<K, V> where K: IntoValue, V: IntoValue {
+    fn into_value(self) -> Value {
+        RHash::from(self).value()
+    }
+}
+
+impl<K, V> TryFromValue for BTreeMap<K, V>
+    where K: TryFromValue + Ord, V: TryFromValue {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RHash::try_from_value(value)?.try_to_btree_map()
+    }
+}
+
+macro_rules! impl_tuple {
+    ( $len:expr => $( $name:ident ),+ ) => {
+        impl<$( $name ),+> IntoValue for ( $( $name, )+ ) where $( $name: IntoValue ),+ {
+            #[allow(non_snake_case)]
+            fn into_value(self) -> Value {
+                let ( $( $name, )+ ) = self;
+                vec![ $( $name.into_value() ),+ ].into_value()
+            }
+        }
+
+        impl<$( $name ),+> TryFromValue for ( $( $name, )+ ) where $( $name: TryFromValue ),+ {
+            fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+                let elements = array_elements(value)?;
+                if elements.len() != $len {
+                    return Err(ConversionError::WrongLength { expected: $len, found: elements.len() });
+                }
+
+                let mut elements = elements.into_iter();
+                Ok(( $( $name::try_from_value(elements.next().unwrap())?, )+ ))
+            }
+        }
+    }
+}
+
+impl_tuple!(1 => A);
+impl_tuple!(2 => A, B);
+impl_tuple!(3 => A, B, C);
+impl_tuple!(4 => A, B, C, D);
+impl_tuple!(5 => A, B, C, D, E);
+impl_tuple!(6 => A, B, C, D, E, F);
+
+/// Packs and unpacks integers as native-endian words, least significant first.
+pub(crate) const INTEGER_PACK_FLAGS: libc::c_int = ffi::INTEGER_PACK_LSWORD_FIRST | ffi::INTEGER_PACK_NATIVE;
+
+/// Splits an `Integer` into its sign and magnitude.
+fn integer_parts(value: Value, target: &'static str) -> Result<(bool, u128), ConversionError> {
+    if ffi::TYPE_P(value.0, ffi::T_FIXNUM) {
+        let value = value.to_i64();
+        return Ok((value < 0, value.unsigned_abs() as u128));
+    }
+    if !value.is_integer() {
+        return Err(ConversionError::wrong_type("Integer", value));
+    }
+
+    let mut magnitude: u128 = 0;
+    let sign = unsafe {
+        ffi::rb_integer_pack(value.0, &mut magnitude as *mut u128 as *mut libc::c_void, 1, 16, 0,
+                             INTEGER_PACK_FLAGS)
+    };
+
+    match sign {
+        -2 | 2 => Err(ConversionError::OutOfRange { target }),
+        sign => Ok((sign < 0, magnitude)),
+    }
+}
+
+/// Gets the elements of a Ruby `Array`.
+fn array_elements(value: Value) -> Result<Vec<Value>, ConversionError> {
+    Ok(RArray::try_from_value(value)?.to_vec())
+}
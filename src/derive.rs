@@ -0,0 +1,128 @@
+//! Exposing Rust types as Ruby classes.
+//!
+//! These traits are usually implemented by the `RubyClass` derive and the
+//! `ruby_methods` attribute from the `derive` feature, and the class defined
+//! with `VM::define_class`.
+//!
+//! ```ignore
+//! #[derive(RubyClass)]
+//! struct Point {
+//!     #[ruby(reader)]
+//!     x: i64,
+//!     #[ruby(accessor)]
+//!     y: i64,
+//! }
+//!
+//! #[ruby_methods]
+//! impl Point {
+//!     pub fn new(x: i64, y: i64) -> Self { Point { x, y } }
+//!
+//!     pub fn length(&self) -> f64 { ((self.x * self.x + self.y * self.y) as f64).sqrt() }
+//!
+//!     #[ruby(name = "origin?")]
+//!     pub fn is_origin(&self) -> bool { self.x == 0 && self.y == 0 }
+//! }
+//!
+//! vm.define_class::<Point>();
+//! ```
+
+use crate::{builder, TypedData};
+
+/// A Rust type exposed as a Ruby class.
+pub trait RubyClass: TypedData {
+    /// The name of the Ruby class.
+    const NAME: &'static str;
+
+    /// Defines the attribute readers and writers for the type's fields.
+    fn define_attributes(class: builder::Class) -> builder::Class;
+}
+
+/// The methods of a Rust type exposed as a Ruby class.
+pub trait RubyMethods: TypedData {
+    /// Defines the methods, including `initialize` if there is a constructor.
+    fn define_methods(class: builder::Class) -> builder::Class;
+}
+
+/// Implementation details of the derive macros.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{classes, ffi, typed_data, ConversionError, ErrorKind, IntoValue, RubyException, TypedData, Value};
+
+    use std::any;
+    use std::cell::{Ref, RefMut};
+
+    /// The return value of an exposed method.
+    pub trait MethodReturn {
+        fn into_return(self) -> Result<Value, ErrorKind>;
+    }
+
+    impl<T> MethodReturn for T where T: IntoValue {
+        fn into_return(self) -> Result<Value, ErrorKind> { Ok(self.into_value()) }
+    }
+
+    impl<T, E> MethodReturn for Result<T, E> where T: IntoValue, E: Into<ErrorKind> {
+        fn into_return(self) -> Result<Value, ErrorKind> {
+            self.map(IntoValue::into_value).map_err(Into::into)
+        }
+    }
+
+    /// The return value of an exposed method returning `Self`.
+    pub trait Wrapped {
+        fn into_wrapped(self, class: Value) -> Result<Value, ErrorKind>;
+    }
+
+    impl<T> Wrapped for T where T: TypedData {
+        fn into_wrapped(self, class: Value) -> Result<Value, ErrorKind> {
+            Ok(typed_data::wrap(class, self))
+        }
+    }
+
+    impl<T, E> Wrapped for Result<T, E> where T: TypedData, E: Into<ErrorKind> {
+        fn into_wrapped(self, class: Value) -> Result<Value, ErrorKind> {
+            Ok(typed_data::wrap(class, self.map_err(Into::into)?))
+        }
+    }
+
+    /// The return value of a constructor.
+    pub trait Constructed<T> {
+        fn into_constructed(self) -> Result<T, ErrorKind>;
+    }
+
+    impl<T> Constructed<T> for T {
+        fn into_constructed(self) -> Result<T, ErrorKind> { Ok(self) }
+    }
+
+    impl<T, E> Constructed<T> for Result<T, E> where E: Into<ErrorKind> {
+        fn into_constructed(self) -> Result<T, ErrorKind> { self.map_err(Into::into) }
+    }
+
+    pub fn check_arity(args: &[Value], expected: usize) -> Result<(), ErrorKind> {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            let message = format!("wrong number of arguments (given {}, expected {})", args.len(), expected);
+            Err(RubyException::new(classes::ArgError(), message).into())
+        }
+    }
+
+    /// The class of a receiver, skipping any singleton class, like `Object#class`.
+    pub fn class_of(receiver: Value) -> Value {
+        Value::from(unsafe { ffi::rb_obj_class(receiver.0) })
+    }
+
+    pub fn borrow<T>(receiver: &Value) -> Result<Ref<'_, T>, ErrorKind> where T: TypedData {
+        receiver.downcast_ref().ok_or_else(|| not_wrapping::<T>(*receiver))
+    }
+
+    pub fn borrow_mut<T>(receiver: &Value) -> Result<RefMut<'_, T>, ErrorKind> where T: TypedData {
+        receiver.downcast_mut().ok_or_else(|| not_wrapping::<T>(*receiver))
+    }
+
+    fn not_wrapping<T>(receiver: Value) -> ErrorKind where T: TypedData {
+        if typed_data::is_borrowed::<T>(receiver) {
+            ConversionError::AlreadyBorrowed.into()
+        } else {
+            ConversionError::wrong_type(any::type_name::<T>(), receiver).into()
+        }
+    }
+}
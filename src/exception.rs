@@ -0,0 +1,410 @@
+//! Ruby exceptions.
+
+use crate::{classes, ffi, libc, ErrorKind, RArray, RootedValue, Value, VM};
+
+use std::fmt;
+
+/// The hidden instance variable holding the cause to raise an exception with.
+///
+/// Not prefixed with `@`, so it is invisible to Ruby code.
+const CAUSE_IVAR: &str = "__rurust_cause__";
+
+/// The most causes captured for an exception.
+///
+/// Ruby prevents circular causes, but don't trust it.
+const MAX_CAUSES: usize = 64;
+
+/// A Ruby exception object.
+///
+/// The exception object is kept alive for as long as this is, so it can
+/// only be used on the VM's thread. Its details are captured up front as
+/// an `ExceptionDetails`, which can be sent to other threads.
+#[derive(Clone)]
+pub struct RubyException {
+    value: RootedValue,
+    details: ExceptionDetails,
+}
+
+/// The details of a Ruby exception, captured as Rust values.
+///
+/// Unlike `RubyException`, this does not reference the exception object,
+/// so it can be sent to other threads and kept after the VM has moved on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionDetails {
+    /// Boxed to keep errors small.
+    inner: Box<Details>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Details {
+    class_path: String,
+    /// The names of the class and its ancestors, excluding anonymous ones.
+    ancestors: Vec<String>,
+    message: String,
+    full_message: String,
+    backtrace: Vec<String>,
+    cause: Option<ExceptionDetails>,
+}
+
+/// A Rust error that maps onto a Ruby exception class.
+///
+/// Usually implemented by an error enum, with each variant mapping onto
+/// a class built with `builder::ExceptionClass`.
+pub trait RubyError: fmt::Display + Sized {
+    /// The full path of the exception class to raise, such as `MyLib::TimeoutError`.
+    fn class_path(&self) -> &'static str;
+
+    /// The fields to store in the exception's instance variables.
+    fn fields(&self) -> Vec<(&'static str, Value)> { Vec::new() }
+
+    /// Recovers the Rust error from a raised exception.
+    ///
+    /// Returns `None` if the exception does not correspond to this type.
+    fn from_exception(exception: &RubyException) -> Option<Self>;
+
+    /// Converts the error into an exception.
+    fn into_exception(self) -> Result<RubyException, ErrorKind> {
+        let class = classes::find(self.class_path())?;
+        let mut exception = RubyException::new(class, self.to_string());
+
+        for (name, value) in self.fields() {
+            exception = exception.with_ivar(&format!("@{}", name), value);
+        }
+        Ok(exception)
+    }
+
+    /// Converts the error into an error that can be returned to Ruby.
+    fn into_error(self) -> ErrorKind {
+        match self.into_exception() {
+            Ok(exception) => ErrorKind::Exception(exception),
+            Err(e) => e,
+        }
+    }
+}
+
+/// A single frame of a backtrace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The file the frame is in.
+    pub file: String,
+    /// The line number, if known.
+    pub line: Option<u32>,
+    /// The method the frame is in, if any.
+    pub method: Option<String>,
+}
+
+impl RubyException {
+    /// Creates a new exception of the given class.
+    ///
+    /// The class can be any exception class, for example one of the
+    /// exceptions in `classes` or a custom class extending `StandardError`.
+    /// The message is used verbatim.
+    pub fn new<S>(class: Value, message: S) -> Self where S: AsRef<str> {
+        let message = message.as_ref();
+
+        let value = Value::from(unsafe {
+            ffi::rb_exc_new(class.0, message.as_ptr() as *const libc::c_char,
+                            message.len() as libc::c_long)
+        });
+        RubyException::capture(value)
+    }
+
+    /// Creates a new exception of the given class, catching any exception
+    /// raised by the class's `initialize`.
+    pub fn try_new<S>(class: Value, message: S) -> Result<Self, ErrorKind> where S: AsRef<str> {
+        let message = message.as_ref();
+        let (ptr, len) = (message.as_ptr() as *const libc::c_char, message.len() as libc::c_long);
+
+        let value = VM::protect(|| Value::from(unsafe { ffi::rb_exc_new(class.0, ptr, len) }))?;
+        Ok(RubyException::capture(value))
+    }
+
+    /// Wraps an exception object, capturing its details.
+    fn capture(value: Value) -> Self {
+        RubyException {
+            value: RootedValue::new(value),
+            details: ExceptionDetails::capture(value, 0),
+        }
+    }
+
+    /// Sets the exception that will be reported as the cause when raised.
+    pub fn with_cause(mut self, cause: RubyException) -> Self {
+        unsafe { ffi::rb_ivar_set(self.value.0, Value::intern(CAUSE_IVAR), cause.value.0) };
+        self.details.inner.cause = Some(cause.details);
+        self
+    }
+
+    /// Sets an instance variable on the exception.
+    pub fn with_ivar(self, name: &str, value: Value) -> Self {
+        self.value.set_ivar(name, value);
+        self
+    }
+
+    /// Raises the exception.
+    ///
+    /// This longjmps straight over any Rust frames, skipping their destructors.
+    /// Inside closure-based methods, return it as an `Err` instead.
+    pub fn raise(self) -> ! {
+        let value = self.value.get();
+        // Nothing else may be dropped once Ruby starts unwinding.
+        drop(self);
+
+        let cause = Value::from(unsafe { ffi::rb_attr_get(value.0, Value::intern(CAUSE_IVAR)) });
+
+        if cause.is_nil() {
+            unsafe { ffi::rb_exc_raise(value.0) }
+        } else {
+            let options = Value::from(unsafe { ffi::rb_hash_new() });
+            unsafe { ffi::rb_hash_aset(options.0, Value::symbol("cause").0, cause.0) };
+
+            let args = [value, options];
+            unsafe {
+                ffi::rb_funcallv_kw(ffi::rb_mKernel, Value::intern("raise"), 2,
+                                    args.as_ptr() as *const _, 1);
+            }
+            unreachable!("Kernel#raise returned");
+        }
+    }
+
+    /// Gets the underlying exception object.
+    pub fn value(&self) -> Value { self.value.get() }
+
+    /// Gets the class of the exception.
+    pub fn class(&self) -> Value { self.value.class() }
+
+    /// Gets the details captured when the exception was created.
+    pub fn details(&self) -> &ExceptionDetails { &self.details }
+
+    /// Releases the exception object, keeping only its details.
+    pub fn into_details(self) -> ExceptionDetails { self.details }
+
+    /// Gets the full path of the exception's class, such as `Errno::ENOENT`.
+    pub fn class_path(&self) -> String { self.details.class_path() }
+
+    /// The value of `Exception#message` when the exception was created.
+    pub fn message(&self) -> String { self.details.message() }
+
+    /// The value of `Exception#full_message` when the exception was created.
+    pub fn full_message(&self) -> String { self.details.full_message() }
+
+    /// Gets the raw backtrace lines.
+    pub fn backtrace_lines(&self) -> Vec<String> { self.details.backtrace_lines() }
+
+    /// Gets the backtrace, innermost frame first.
+    pub fn backtrace(&self) -> Vec<Frame> { self.details.backtrace() }
+
+    /// Checks if the exception is an instance of a class or one of its subclasses.
+    pub fn is_kind_of(&self, class: Value) -> bool {
+        Value::from(unsafe { ffi::rb_obj_is_kind_of(self.value.0, class.0) }).is_true()
+    }
+
+    /// Checks if the exception is an instance of the class at the given path,
+    /// or one of its subclasses.
+    pub fn is_kind_of_path(&self, class_path: &str) -> bool {
+        self.details.is_kind_of_path(class_path)
+    }
+
+    /// Gets the value of a field, as set by `RubyError::fields`.
+    pub fn field(&self, name: &str) -> Value {
+        self.value.get_ivar(&format!("@{}", name))
+    }
+
+    /// Gets the exception that was being handled when this one was raised.
+    pub fn cause(&self) -> Option<RubyException> {
+        match self.value.try_call_no_args("cause") {
+            Ok(cause) if !cause.is_nil() => Some(RubyException::capture(cause)),
+            _ => None,
+        }
+    }
+
+    /// Gets the chain of causes, starting with the direct cause.
+    pub fn causes(&self) -> Vec<RubyException> {
+        let mut causes = Vec::new();
+        let mut current = self.cause();
+
+        while let Some(cause) = current {
+            if causes.len() == MAX_CAUSES || causes.contains(&cause) { break; }
+
+            current = cause.cause();
+            causes.push(cause);
+        }
+        causes
+    }
+}
+
+impl ExceptionDetails {
+    fn capture(value: Value, depth: usize) -> Self {
+        let string = |method_name| call_quietly(value, method_name).and_then(|s| s.as_string());
+
+        let backtrace = call_quietly(value, "backtrace")
+            .and_then(|backtrace| backtrace.try_convert::<Option<Vec<String>>>().ok().flatten())
+            .unwrap_or_default();
+        let ancestors = call_quietly(value.class(), "ancestors")
+            .and_then(RArray::from_value)
+            .map(|ancestors| ancestors.iter()
+                .filter_map(|module| call_quietly(module, "name")?.as_string())
+                .collect())
+            .unwrap_or_default();
+        let cause = match call_quietly(value, "cause") {
+            Some(cause) if !cause.is_nil() && depth < MAX_CAUSES => {
+                Some(ExceptionDetails::capture(cause, depth + 1))
+            },
+            _ => None,
+        };
+
+        let class_path = value.class_name();
+        let message = string("message").unwrap_or_default();
+        let full_message = string("full_message").unwrap_or_else(|| format!("{}: {}", class_path, message));
+
+        ExceptionDetails {
+            inner: Box::new(Details { class_path, ancestors, message, full_message, backtrace, cause }),
+        }
+    }
+
+    /// Gets the full path of the exception's class, such as `Errno::ENOENT`.
+    pub fn class_path(&self) -> String { self.inner.class_path.clone() }
+
+    /// The value of `Exception#message`.
+    pub fn message(&self) -> String { self.inner.message.clone() }
+
+    /// The value of `Exception#full_message`.
+    ///
+    /// Includes the message, class and backtrace, formatted as Ruby
+    /// would print them for an uncaught exception.
+    pub fn full_message(&self) -> String { self.inner.full_message.clone() }
+
+    /// Gets the raw backtrace lines.
+    ///
+    /// Empty if the exception had not been raised when it was captured.
+    pub fn backtrace_lines(&self) -> Vec<String> { self.inner.backtrace.clone() }
+
+    /// Gets the backtrace, innermost frame first.
+    pub fn backtrace(&self) -> Vec<Frame> {
+        self.inner.backtrace.iter().map(|line| Frame::parse(line)).collect()
+    }
+
+    /// Checks if the exception's class, or one of its ancestors, has the given path.
+    pub fn is_kind_of_path(&self, class_path: &str) -> bool {
+        self.inner.ancestors.iter().any(|ancestor| ancestor == class_path)
+    }
+
+    /// Gets the details of the exception's cause.
+    pub fn cause(&self) -> Option<&ExceptionDetails> {
+        self.inner.cause.as_ref()
+    }
+
+    /// Gets the chain of causes, starting with the direct cause.
+    pub fn causes(&self) -> Vec<&ExceptionDetails> {
+        std::iter::successors(self.cause(), |cause| cause.cause()).collect()
+    }
+}
+
+/// Calls a method while capturing an exception, ignoring anything it raises.
+///
+/// Exceptions raised here are not captured in turn, as that could recurse forever.
+fn call_quietly(value: Value, method_name: &str) -> Option<Value> {
+    let id = Value::intern(method_name);
+    let result = VM::protect_raw(|| Value::from(unsafe { ffi::rb_funcallv(value.0, id, 0, std::ptr::null()) }));
+
+    match result {
+        Ok(result) => Some(result),
+        Err(state) => {
+            if state == ffi::TAG_RAISE || state == ffi::TAG_FATAL {
+                unsafe { ffi::rb_set_errinfo(ffi::Qnil) };
+            }
+            None
+        },
+    }
+}
+
+impl Frame {
+    /// Parses a backtrace line such as `foo.rb:12:in 'bar'`.
+    pub fn parse(line: &str) -> Self {
+        let (location, method) = match line.find(":in ") {
+            Some(idx) => {
+                let method = line[idx + 4..].trim_matches(|c| c == '`' || c == '\'');
+                (&line[..idx], Some(method.to_owned()))
+            },
+            None => (line, None),
+        };
+
+        let mut parts = location.rsplitn(2, ':');
+        let last = parts.next().unwrap_or("");
+
+        match (last.parse(), parts.next()) {
+            (Ok(line), Some(file)) => Frame { file: file.to_owned(), line: Some(line), method },
+            _ => Frame { file: location.to_owned(), line: None, method },
+        }
+    }
+}
+
+impl From<RubyException> for ErrorKind {
+    fn from(exception: RubyException) -> Self {
+        ErrorKind::Exception(exception)
+    }
+}
+
+impl From<Value> for RubyException {
+    fn from(value: Value) -> Self {
+        RubyException::capture(value)
+    }
+}
+
+impl From<RubyException> for ExceptionDetails {
+    fn from(exception: RubyException) -> Self {
+        exception.into_details()
+    }
+}
+
+impl PartialEq for RubyException {
+    fn eq(&self, other: &Self) -> bool {
+        self.value.get() == other.value.get()
+    }
+}
+
+impl Eq for RubyException { }
+
+impl fmt::Display for RubyException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.details, fmt)
+    }
+}
+
+impl fmt::Debug for RubyException {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+
+impl std::error::Error for RubyException {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.details.cause().map(|cause| cause as _)
+    }
+}
+
+impl fmt::Display for ExceptionDetails {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.inner.class_path, self.inner.message)
+    }
+}
+
+impl std::error::Error for ExceptionDetails {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause().map(|cause| cause as _)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Frame;
+
+    #[test]
+    fn can_parse_backtrace_frames() {
+        assert_eq!(Frame { file: "foo.rb".to_owned(), line: Some(12), method: Some("bar".to_owned()) },
+                   Frame::parse("foo.rb:12:in `bar'"));
+        assert_eq!(Frame { file: "C:/foo.rb".to_owned(), line: Some(3), method: Some("Foo#bar".to_owned()) },
+                   Frame::parse("C:/foo.rb:3:in 'Foo#bar'"));
+        assert_eq!(Frame { file: "(eval)".to_owned(), line: Some(1), method: None },
+                   Frame::parse("(eval):1"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Running Ruby from multi-threaded programs.
+//!
+//! Ruby can only be called from the thread that created the VM. A
+//! `VmExecutor` owns that thread, and hands out `ExecutorHandle`s that
+//! can submit work to it from any thread.
+//!
+//! Ruby values are not `Send`, so results must be converted into Rust
+//! values, and errors into `OwnedError`s, before they are returned.
+
+use crate::{OwnedError, VMBuilder, VM};
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// The stack size of the Ruby thread.
+///
+/// Ruby is a lot hungrier for stack than the default Rust thread allows.
+const STACK_SIZE: usize = 16 * 1024 * 1024;
+
+type Job = Box<dyn FnOnce(&mut VM) + Send>;
+
+enum Message {
+    Run(Job),
+    Shutdown,
+}
+
+/// Owns a dedicated thread running the Ruby VM.
+///
+/// Dropping the executor finishes any submitted work, then
+/// destroys the VM and joins the thread.
+pub struct VmExecutor {
+    handle: ExecutorHandle,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// A handle for submitting work to a `VmExecutor` from any thread.
+#[derive(Clone)]
+pub struct ExecutorHandle {
+    sender: mpsc::Sender<Message>,
+}
+
+/// The eventual result of `ExecutorHandle::run_async`.
+pub struct RunFuture<T> {
+    state: Arc<Mutex<FutureState<T>>>,
+}
+
+struct FutureState<T> {
+    result: Option<thread::Result<Result<T, OwnedError>>>,
+    waker: Option<Waker>,
+}
+
+/// Completes a `RunFuture`, moved into the job that produces its result.
+///
+/// If the job is dropped without running, because the executor shut down
+/// first, the future is resolved with an error instead of never waking.
+struct Completion<T> {
+    state: Arc<Mutex<FutureState<T>>>,
+    done: bool,
+}
+
+impl VmExecutor {
+    /// Starts a Ruby thread with a VM with the default options.
+    pub fn new() -> Result<Self, OwnedError> {
+        VmExecutor::with_builder(VMBuilder::new())
+    }
+
+    /// Starts a Ruby thread with a VM configured by a builder.
+    pub fn with_builder(builder: VMBuilder) -> Result<Self, OwnedError> {
+        let (sender, receiver) = mpsc::channel();
+        let (init_sender, init_receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("ruby".to_owned())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut vm = match builder.build() {
+                    Ok(vm) => {
+                        init_sender.send(Ok(())).unwrap();
+                        vm
+                    },
+                    Err(e) => {
+                        init_sender.send(Err(OwnedError::from(e))).unwrap();
+                        return;
+                    },
+                };
+
+                while let Ok(Message::Run(job)) = receiver.recv() {
+                    job(&mut vm);
+                }
+            })
+            .map_err(|e| OwnedError::VM(format!("could not start Ruby thread: {}", e)))?;
+
+        match init_receiver.recv() {
+            Ok(Ok(())) => Ok(VmExecutor {
+                handle: ExecutorHandle { sender },
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(..) => Err(OwnedError::VM("Ruby thread died during startup".to_owned())),
+        }
+    }
+
+    /// Gets a handle for submitting work.
+    pub fn handle(&self) -> ExecutorHandle {
+        self.handle.clone()
+    }
+}
+
+impl ExecutorHandle {
+    /// Runs a function on the Ruby thread, blocking until it finishes.
+    ///
+    /// Panics in the function are propagated to the caller.
+    /// Panics if the executor has been dropped.
+    ///
+    /// Ruby values cannot be returned, as they are not `Send`:
+    ///
+    /// ```compile_fail
+    /// # fn run(handle: rurust::ExecutorHandle) {
+    /// let value = handle.run(|vm| vm.eval("1 + 2"));
+    /// # }
+    /// ```
+    pub fn run<F, T>(&self, f: F) -> T
+        where F: FnOnce(&mut VM) -> T + Send + 'static,
+              T: Send + 'static {
+        let (sender, receiver) = mpsc::channel();
+
+        self.submit(Box::new(move |vm: &mut VM| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(vm)));
+            let _ = sender.send(result);
+        }));
+
+        match receiver.recv().expect("the VM executor has shut down") {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Runs a function on the Ruby thread without blocking.
+    ///
+    /// Returns a future resolving to the function's result. Panics in
+    /// the function are propagated when the future is polled. If the
+    /// executor shuts down before running the function, the future
+    /// resolves to an error.
+    pub fn run_async<F, T>(&self, f: F) -> RunFuture<T>
+        where F: FnOnce(&mut VM) -> T + Send + 'static,
+              T: Send + 'static {
+        let state = Arc::new(Mutex::new(FutureState { result: None, waker: None }));
+        let mut completion = Completion { state: state.clone(), done: false };
+
+        let job = Box::new(move |vm: &mut VM| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(vm)));
+            completion.complete(result.map(Ok));
+        });
+
+        // A job that cannot be sent is dropped, which resolves the future.
+        let _ = self.sender.send(Message::Run(job));
+
+        RunFuture { state }
+    }
+
+    fn submit(&self, job: Job) {
+        self.sender.send(Message::Run(job)).expect("the VM executor has shut down");
+    }
+}
+
+impl<T> Completion<T> {
+    fn complete(&mut self, result: thread::Result<Result<T, OwnedError>>) {
+        let mut state = self.state.lock().unwrap();
+
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.done = true;
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.complete(Ok(Err(OwnedError::VM("executor shut down".to_owned()))));
+        }
+    }
+}
+
+impl<T> Future for RunFuture<T> {
+    type Output = Result<T, OwnedError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T, OwnedError>> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl Drop for VmExecutor {
+    fn drop(&mut self) {
+        let _ = self.handle.sender.send(Message::Shutdown);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExecutorHandle, Message};
+    use crate::OwnedError;
+
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{mpsc, Arc};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) { self.0.unpark(); }
+    }
+
+    fn block_on<F>(mut future: F) -> F::Output where F: Future + Unpin {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match Pin::new(&mut future).poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    // These run without a VM, which only one test per process can have.
+    #[test]
+    fn resolves_jobs_submitted_after_shutdown() {
+        let (sender, receiver) = mpsc::channel();
+        drop(receiver);
+
+        let handle = ExecutorHandle { sender };
+        match block_on(handle.run_async(|_| 1)) {
+            Err(OwnedError::VM(message)) => assert_eq!("executor shut down", message),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn resolves_queued_jobs_dropped_by_shutdown() {
+        let (sender, receiver) = mpsc::channel();
+        let handle = ExecutorHandle { sender };
+
+        let future = handle.run_async(|_| 1);
+        let waiter = thread::spawn(move || block_on(future).is_err());
+
+        // Stands in for the Ruby thread exiting with jobs still queued.
+        handle.sender.send(Message::Shutdown).unwrap();
+        drop(receiver);
+        assert!(waiter.join().unwrap());
+    }
+}
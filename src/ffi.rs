@@ -0,0 +1,207 @@
+//! Raw bindings to MRI.
+//!
+//! Re-exports everything from `mri-sys` along with the handful of
+//! functions that crate does not (yet) declare.
+
+pub use mri_sys::*;
+
+/// The jump tag used when an exception is raised.
+pub const TAG_RAISE: libc::c_int = 0x6;
+/// The jump tag used for fatal errors.
+pub const TAG_FATAL: libc::c_int = 0x8;
+
+/// Flags for `rb_integer_pack` and `rb_integer_unpack`.
+pub const INTEGER_PACK_LSWORD_FIRST: libc::c_int = 0x02;
+pub const INTEGER_PACK_NATIVE: libc::c_int = 0x40;
+pub const INTEGER_PACK_2COMP: libc::c_int = 0x80;
+
+/// Tells `rb_hash_foreach` to carry on iterating.
+pub const ST_CONTINUE: libc::c_int = 0;
+
+/// Tells Ruby it may free typed data during GC sweeping.
+pub const RUBY_TYPED_FREE_IMMEDIATELY: INNER_VALUE = 1;
+
+/// The oldest and newest MRI versions, as `(major, minor)`, whose object
+/// layouts match the accessors below. Checked when the VM is created.
+pub const SUPPORTED_API_VERSIONS: ((libc::c_int, libc::c_int), (libc::c_int, libc::c_int)) = ((3, 2), (3, 4));
+
+/// Checks the linked Ruby against `SUPPORTED_API_VERSIONS`.
+pub fn check_api_version() -> Result<(), String> {
+    let version = unsafe { (ruby_api_version[0], ruby_api_version[1]) };
+    let (oldest, newest) = SUPPORTED_API_VERSIONS;
+
+    if version >= oldest && version <= newest {
+        Ok(())
+    } else {
+        Err(format!("unsupported Ruby API version {}.{} (supported: {}.{} to {}.{})",
+                    version.0, version.1, oldest.0, oldest.1, newest.0, newest.1))
+    }
+}
+
+/// The start of an `OnigEncodingType`, up to the encoding's name.
+///
+/// This has not changed since Ruby 2.0.
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct rb_encoding {
+    precise_mbc_enc_len: *const libc::c_void,
+    name: *const libc::c_char,
+}
+
+/// The name of an encoding, like the `rb_enc_name` macro.
+pub unsafe fn rb_enc_name(enc: *mut rb_encoding) -> *const libc::c_char {
+    (*enc).name
+}
+
+// The object layouts below are those of MRI 3.2 to 3.4 (see
+// `SUPPORTED_API_VERSIONS`). Ruby only provides these accessors as inline
+// functions, so they are reimplemented here and must be checked against
+// each new Ruby release.
+
+/// The first flag bit available to each object type.
+const RUBY_FL_USHIFT: INNER_VALUE = 12;
+/// Set on arrays whose elements are stored inside the object itself.
+const RARRAY_EMBED_FLAG: INNER_VALUE = 1 << (RUBY_FL_USHIFT + 1);
+/// The flag bits holding the length of an embedded array.
+const RARRAY_EMBED_LEN_SHIFT: INNER_VALUE = RUBY_FL_USHIFT + 3;
+const RARRAY_EMBED_LEN_MASK: INNER_VALUE = 0x7f << RARRAY_EMBED_LEN_SHIFT;
+
+/// The start of `struct RArray`, up to the length of a heap array.
+#[repr(C)]
+struct RArrayHeader {
+    flags: INNER_VALUE,
+    klass: INNER_VALUE,
+    len: libc::c_long,
+}
+
+/// The start of `struct RString`, up to its length.
+///
+/// Since Ruby 3.2 the length is stored here for embedded strings too.
+#[repr(C)]
+struct RStringHeader {
+    flags: INNER_VALUE,
+    klass: INNER_VALUE,
+    len: libc::c_long,
+}
+
+/// The length of an array, like the `RARRAY_LEN` macro.
+///
+/// `ary` must be an `Array`.
+#[allow(non_snake_case)]
+pub unsafe fn RARRAY_LEN(ary: VALUE) -> libc::c_long {
+    let header = &*(ary.0 as *const RArrayHeader);
+
+    if header.flags & RARRAY_EMBED_FLAG != 0 {
+        ((header.flags & RARRAY_EMBED_LEN_MASK) >> RARRAY_EMBED_LEN_SHIFT) as libc::c_long
+    } else {
+        header.len
+    }
+}
+
+/// The length of a string in bytes, like the `RSTRING_LEN` macro.
+///
+/// `str` must be a `String`.
+#[allow(non_snake_case)]
+pub unsafe fn RSTRING_LEN(str: VALUE) -> libc::c_long {
+    (*(str.0 as *const RStringHeader)).len
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct rb_data_type_function {
+    pub dmark: Option<extern "C" fn(*mut libc::c_void)>,
+    pub dfree: Option<extern "C" fn(*mut libc::c_void)>,
+    pub dsize: Option<extern "C" fn(*const libc::c_void) -> libc::size_t>,
+    pub dcompact: Option<extern "C" fn(*mut libc::c_void)>,
+    pub reserved: [*mut libc::c_void; 1],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct rb_data_type_t {
+    pub wrap_struct_name: *const libc::c_char,
+    pub function: rb_data_type_function,
+    pub parent: *const rb_data_type_t,
+    pub data: *mut libc::c_void,
+    pub flags: VALUE,
+}
+
+extern "C" {
+    pub static ruby_api_version: [libc::c_int; 3];
+
+    pub fn rb_frame_method_id_and_class(idp: *mut ID, klassp: *mut VALUE) -> libc::c_int;
+    pub fn rb_singleton_class(_: VALUE) -> VALUE;
+
+    pub fn rb_exc_new(klass: VALUE, ptr: *const libc::c_char, len: libc::c_long) -> VALUE;
+    pub fn rb_exc_raise(_: VALUE) -> !;
+    pub fn rb_funcallv_kw(recv: VALUE, mid: ID, argc: libc::c_int, argv: *const VALUE, kw_splat: libc::c_int) -> VALUE;
+    pub fn rb_jump_tag(state: libc::c_int) -> !;
+    pub fn rb_attr_get(obj: VALUE, id: ID) -> VALUE;
+    pub fn rb_ivar_set(obj: VALUE, id: ID, value: VALUE) -> VALUE;
+    pub fn rb_obj_is_kind_of(obj: VALUE, class: VALUE) -> VALUE;
+    pub fn rb_obj_class(obj: VALUE) -> VALUE;
+
+    pub fn rb_define_alloc_func(klass: VALUE, func: extern "C" fn(VALUE) -> VALUE);
+    pub fn rb_data_typed_object_wrap(klass: VALUE, datap: *mut libc::c_void, ty: *const rb_data_type_t) -> VALUE;
+    pub fn rb_typeddata_is_kind_of(obj: VALUE, ty: *const rb_data_type_t) -> libc::c_int;
+    pub fn rb_check_typeddata(obj: VALUE, ty: *const rb_data_type_t) -> *mut libc::c_void;
+    pub fn rb_block_given_p() -> libc::c_int;
+    pub fn rb_keyword_given_p() -> libc::c_int;
+    pub fn rb_remove_method(klass: VALUE, name: *const libc::c_char);
+    pub fn rb_define_private_method(klass: VALUE, name: *const libc::c_char,
+                                    func: *mut extern "C" fn() -> VALUE, argc: libc::c_int);
+    pub fn rb_define_protected_method(klass: VALUE, name: *const libc::c_char,
+                                      func: *mut extern "C" fn() -> VALUE, argc: libc::c_int);
+    pub fn rb_block_proc() -> VALUE;
+    pub fn rb_block_call(obj: VALUE, mid: ID, argc: libc::c_int, argv: *const VALUE,
+                         bl_proc: extern "C" fn(VALUE, VALUE, libc::c_int, *const VALUE, VALUE) -> VALUE,
+                         data2: VALUE) -> VALUE;
+    pub fn rb_proc_new(func: extern "C" fn(VALUE, VALUE, libc::c_int, *const VALUE, VALUE) -> VALUE,
+                       val: VALUE) -> VALUE;
+    pub fn rb_func_lambda_new(func: extern "C" fn(VALUE, VALUE, libc::c_int, *const VALUE, VALUE) -> VALUE,
+                              val: VALUE, min_argc: libc::c_int, max_argc: libc::c_int) -> VALUE;
+    pub fn rb_load(path: VALUE, wrap: libc::c_int);
+    pub fn rb_gvar_readonly_setter(val: VALUE, id: ID, data: *mut VALUE);
+
+    pub fn rb_thread_call_without_gvl(func: extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+                                      data1: *mut libc::c_void,
+                                      ubf: Option<extern "C" fn(*mut libc::c_void)>,
+                                      data2: *mut libc::c_void) -> *mut libc::c_void;
+
+    pub fn rb_gc_mark(_: VALUE);
+    // Declared with the wrong signature upstream.
+    pub fn rb_gc_register_mark_object(obj: VALUE);
+
+    pub fn rb_ll2inum(_: libc::c_longlong) -> VALUE;
+    pub fn rb_ull2inum(_: libc::c_ulonglong) -> VALUE;
+    pub fn rb_integer_pack(val: VALUE, words: *mut libc::c_void, numwords: libc::size_t, wordsize: libc::size_t,
+                           nails: libc::size_t, flags: libc::c_int) -> libc::c_int;
+    pub fn rb_integer_unpack(words: *const libc::c_void, numwords: libc::size_t, wordsize: libc::size_t,
+                             nails: libc::size_t, flags: libc::c_int) -> VALUE;
+    #[cfg_attr(not(feature = "num-bigint"), allow(dead_code))]
+    pub fn rb_absint_size(val: VALUE, nlz_bits_ret: *mut libc::c_int) -> libc::size_t;
+
+    pub fn rb_str_new(ptr: *const libc::c_char, len: libc::c_long) -> VALUE;
+    pub fn rb_utf8_str_new(ptr: *const libc::c_char, len: libc::c_long) -> VALUE;
+    pub fn rb_enc_str_new(ptr: *const libc::c_char, len: libc::c_long, enc: *mut rb_encoding) -> VALUE;
+    pub fn rb_enc_find_index(name: *const libc::c_char) -> libc::c_int;
+    pub fn rb_enc_from_index(idx: libc::c_int) -> *mut rb_encoding;
+    pub fn rb_enc_get(obj: VALUE) -> *mut rb_encoding;
+    pub fn rb_str_to_interned_str(str: VALUE) -> VALUE;
+    pub fn rb_obj_frozen_p(obj: VALUE) -> VALUE;
+
+    pub fn rb_ary_new() -> VALUE;
+    pub fn rb_ary_new_capa(capa: libc::c_long) -> VALUE;
+    pub fn rb_ary_push(ary: VALUE, item: VALUE) -> VALUE;
+    pub fn rb_ary_pop(ary: VALUE) -> VALUE;
+    pub fn rb_ary_entry(ary: VALUE, offset: libc::c_long) -> VALUE;
+    pub fn rb_ary_store(ary: VALUE, idx: libc::c_long, val: VALUE);
+
+    pub fn rb_hash_new() -> VALUE;
+    pub fn rb_hash_aref(hash: VALUE, key: VALUE) -> VALUE;
+    pub fn rb_hash_aset(hash: VALUE, key: VALUE, value: VALUE) -> VALUE;
+    pub fn rb_hash_lookup2(hash: VALUE, key: VALUE, default: VALUE) -> VALUE;
+    pub fn rb_hash_delete(hash: VALUE, key: VALUE) -> VALUE;
+    pub fn rb_hash_size(hash: VALUE) -> VALUE;
+    pub fn rb_hash_foreach(hash: VALUE, func: extern "C" fn(VALUE, VALUE, VALUE) -> libc::c_int, arg: VALUE);
+}
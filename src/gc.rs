@@ -0,0 +1,88 @@
+//! Keeping Ruby values alive.
+//!
+//! Ruby's garbage collector scans the native stack conservatively, so a
+//! `Value` held in a local variable is never collected. It does not know
+//! about Rust's heap though. A `Value` stored in a `Box`, a `Vec`, a struct
+//! field, a static or a closure's captures can be collected the next time
+//! Ruby allocates, leaving a dangling reference.
+//!
+//! Values stored anywhere other than the stack must be either
+//!
+//! * rooted with `RootedValue`,
+//! * marked by a `TypedData` implementation's `mark` hook, or
+//! * reachable from Ruby, for example through a constant or global.
+//!
+//! APIs in this crate that take a `Value` only need it to be alive for the
+//! duration of the call, unless their documentation says otherwise. The
+//! class and module builders root every value handed to them, such as
+//! parents, base classes, modules and constants, until they are built.
+
+use crate::{ffi, Value};
+
+use std::marker::PhantomData;
+use std::{fmt, ops};
+
+/// A value that is kept alive until dropped.
+///
+/// Registers its address with the garbage collector on creation and
+/// unregisters it on drop. Must be dropped on the Ruby thread.
+pub struct RootedValue {
+    slot: Box<Value>,
+    /// Registration is not thread safe.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl RootedValue {
+    /// Roots a value.
+    pub fn new(value: Value) -> Self {
+        let mut slot = Box::new(value);
+        unsafe { ffi::rb_gc_register_address(&mut slot.0 as *mut _) };
+
+        RootedValue { slot, _not_send: PhantomData }
+    }
+
+    /// Gets the value.
+    pub fn get(&self) -> Value { *self.slot }
+
+    /// Replaces the value.
+    pub fn set(&mut self, value: Value) {
+        *self.slot = value;
+    }
+}
+
+/// Keeps a value alive for as long as the VM exists.
+///
+/// Cheaper than a `RootedValue` for values that are never released.
+pub fn register_permanently(value: Value) {
+    unsafe { ffi::rb_gc_register_mark_object(value.0) }
+}
+
+impl ops::Deref for RootedValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value { &self.slot }
+}
+
+impl Clone for RootedValue {
+    fn clone(&self) -> Self {
+        RootedValue::new(self.get())
+    }
+}
+
+impl Drop for RootedValue {
+    fn drop(&mut self) {
+        unsafe { ffi::rb_gc_unregister_address(&mut self.slot.0 as *mut _) };
+    }
+}
+
+impl From<Value> for RootedValue {
+    fn from(value: Value) -> Self {
+        RootedValue::new(value)
+    }
+}
+
+impl fmt::Debug for RootedValue {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(fmt)
+    }
+}
@@ -0,0 +1,100 @@
+//! Global variables backed by Rust closures.
+//!
+//! Works like closure-based methods. Every variable shares the same getter
+//! and setter entry points. A variable's closures and stored value are kept
+//! in a Ruby object, which is held in the variable's storage. Ruby marks the
+//! storage of every global, so the closures live as long as the variable.
+
+use crate::{callback, classes, ffi, typed_data, util, ErrorKind, TypedData, Value};
+
+use std::cell::{Ref, RefMut};
+use std::rc::Rc;
+
+/// Gets the value of a variable, given its stored value.
+pub type GetFn = dyn Fn(Value) -> Result<Value, ErrorKind>;
+/// Sets the value of a variable, returning the value to store.
+pub type SetFn = dyn Fn(Value) -> Result<Value, ErrorKind>;
+
+struct Hooks {
+    stored: Value,
+    get: Rc<GetFn>,
+    set: Rc<SetFn>,
+}
+
+impl TypedData for Hooks {
+    fn mark(&self) {
+        typed_data::mark(self.stored);
+    }
+}
+
+/// Defines a global variable with its own storage.
+pub fn define_hooked(name: &str, initial: Value, get: Box<GetFn>, set: Box<SetFn>) {
+    define(name, Hooks { stored: initial, get: get.into(), set: set.into() }, false);
+}
+
+/// Defines a global variable without storage.
+///
+/// Without a setter, assigning to the variable raises a `NameError`.
+pub fn define_virtual(name: &str, get: Box<GetFn>, set: Option<Box<SetFn>>) {
+    let readonly = set.is_none();
+    let set = set.unwrap_or_else(|| Box::new(Ok));
+    define(name, Hooks { stored: Value::nil(), get: get.into(), set: set.into() }, readonly);
+}
+
+fn define(name: &str, hooks: Hooks, readonly: bool) {
+    // Ruby marks the storage, but never frees it.
+    let storage = Box::into_raw(Box::new(typed_data::wrap(classes::Object(), hooks)));
+
+    unsafe {
+        ffi::rb_define_hooked_variable(
+            util::c_string(name).as_ptr(),
+            storage as *mut ffi::VALUE,
+            getter as *mut _,
+            if readonly { ffi::rb_gvar_readonly_setter as *mut _ } else { setter as *mut _ },
+        );
+    }
+}
+
+fn hooks(data: &Value) -> Result<Ref<'_, Hooks>, ErrorKind> {
+    data.downcast_ref::<Hooks>().ok_or_else(in_use)
+}
+
+fn hooks_mut(data: &Value) -> Result<RefMut<'_, Hooks>, ErrorKind> {
+    data.downcast_mut::<Hooks>().ok_or_else(in_use)
+}
+
+fn in_use() -> ErrorKind {
+    ErrorKind::VM("global variable hooks are already borrowed".to_owned())
+}
+
+extern "C" fn getter(_: ffi::ID, data: *mut Value) -> Value {
+    let data = unsafe { &*data };
+
+    // Don't borrow the hooks during the call, the getter may assign the variable.
+    let result = callback::call(|| {
+        let (get, stored) = {
+            let hooks = hooks(data)?;
+            (hooks.get.clone(), hooks.stored)
+        };
+        get(stored)
+    });
+
+    match result {
+        Ok(value) => value,
+        Err(e) => callback::raise(e),
+    }
+}
+
+extern "C" fn setter(value: Value, _: ffi::ID, data: *mut Value) {
+    let data = unsafe { &*data };
+
+    let result = callback::call(|| {
+        let set = hooks(data)?.set.clone();
+        hooks_mut(data)?.stored = set(value)?;
+        Ok(Value::nil())
+    });
+
+    if let Err(e) = result {
+        callback::raise(e);
+    }
+}
@@ -0,0 +1,198 @@
+//! Ruby hashes.
+
+use crate::{ffi, libc, ConversionError, IntoValue, RArray, TryFromValue, Value};
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::{fmt, vec};
+
+/// A Ruby `Hash`.
+///
+/// Like `Value`, this is a plain reference that does not keep the hash alive.
+///
+/// Ruby raises `FrozenError` straight through the caller if a frozen
+/// hash is modified.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct RHash(Value);
+
+/// An iterator over the entries of a hash.
+///
+/// Iterates over a snapshot taken when the iterator was created.
+pub struct Iter {
+    /// The snapshot lives on the Rust heap, so keep the hash on the
+    /// stack to keep the entries alive.
+    _hash: RHash,
+    entries: vec::IntoIter<(Value, Value)>,
+}
+
+impl RHash {
+    /// Creates an empty hash.
+    pub fn new() -> Self {
+        RHash(Value::from(unsafe { ffi::rb_hash_new() }))
+    }
+
+    /// Views a value as a hash.
+    ///
+    /// Returns `None` if the value is not a `Hash`.
+    pub fn from_value(value: Value) -> Option<Self> {
+        if value.is_hash() { Some(RHash(value)) } else { None }
+    }
+
+    /// Gets the underlying hash object.
+    pub fn value(&self) -> Value { self.0 }
+
+    /// Gets the number of entries.
+    pub fn len(&self) -> usize {
+        Value::from(unsafe { ffi::rb_hash_size(self.0 .0) }).to_i64() as usize
+    }
+
+    /// Checks if the hash has no entries.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Inserts an entry, replacing any previous value for the key.
+    pub fn insert<K, V>(&self, key: K, value: V) where K: IntoValue, V: IntoValue {
+        unsafe { ffi::rb_hash_aset(self.0 .0, key.into_value().0, value.into_value().0) };
+    }
+
+    /// Gets the value for a key.
+    ///
+    /// Returns `None` if the key is missing, ignoring the hash's default.
+    pub fn get<K>(&self, key: K) -> Option<Value> where K: IntoValue {
+        let value = unsafe { ffi::rb_hash_lookup2(self.0 .0, key.into_value().0, ffi::Qundef) };
+
+        if value == ffi::Qundef { None } else { Some(Value::from(value)) }
+    }
+
+    /// Gets the value for a key, falling back to the hash's default like `Hash#[]`.
+    pub fn get_or_default<K>(&self, key: K) -> Value where K: IntoValue {
+        Value::from(unsafe { ffi::rb_hash_aref(self.0 .0, key.into_value().0) })
+    }
+
+    /// Checks if the hash has an entry for a key.
+    pub fn contains_key<K>(&self, key: K) -> bool where K: IntoValue {
+        self.get(key).is_some()
+    }
+
+    /// Removes an entry, returning its value.
+    pub fn remove<K>(&self, key: K) -> Option<Value> where K: IntoValue {
+        let key = key.into_value();
+        let value = self.get(key)?;
+
+        unsafe { ffi::rb_hash_delete(self.0 .0, key.0) };
+        Some(value)
+    }
+
+    /// Gets the keys, in insertion order.
+    pub fn keys(&self) -> RArray {
+        self.iter().map(|(key, _)| key).collect()
+    }
+
+    /// Gets the values, in insertion order.
+    pub fn values(&self) -> RArray {
+        self.iter().map(|(_, value)| value).collect()
+    }
+
+    /// Iterates over the entries, in insertion order.
+    pub fn iter(&self) -> Iter {
+        extern "C" fn push_entry(key: ffi::VALUE, value: ffi::VALUE, entries: ffi::VALUE) -> libc::c_int {
+            let entries = unsafe { &mut *(entries.0 as *mut Vec<(Value, Value)>) };
+            entries.push((Value::from(key), Value::from(value)));
+            ffi::ST_CONTINUE
+        }
+
+        let mut entries: Vec<(Value, Value)> = Vec::with_capacity(self.len());
+        unsafe {
+            ffi::rb_hash_foreach(self.0 .0, push_entry,
+                                 ffi::VALUE(&mut entries as *mut _ as ffi::INNER_VALUE));
+        }
+        Iter { _hash: *self, entries: entries.into_iter() }
+    }
+
+    /// Gets the value returned for missing keys.
+    pub fn default(&self) -> Value {
+        self.0.call_no_args("default")
+    }
+
+    /// Sets the value returned for missing keys.
+    pub fn set_default<V>(&self, value: V) where V: IntoValue {
+        self.0.send("default=", &[value.into_value()]);
+    }
+
+    /// Converts every entry into Rust values.
+    pub fn try_to_hash_map<K, V>(&self) -> Result<HashMap<K, V>, ConversionError>
+        where K: TryFromValue + Eq + Hash, V: TryFromValue {
+        self.iter().map(|(key, value)| Ok((K::try_from_value(key)?, V::try_from_value(value)?))).collect()
+    }
+
+    /// Converts every entry into Rust values, sorted by key.
+    pub fn try_to_btree_map<K, V>(&self) -> Result<BTreeMap<K, V>, ConversionError>
+        where K: TryFromValue + Ord, V: TryFromValue {
+        self.iter().map(|(key, value)| Ok((K::try_from_value(key)?, V::try_from_value(value)?))).collect()
+    }
+}
+
+impl Default for RHash {
+    fn default() -> Self { RHash::new() }
+}
+
+impl Iterator for Iter {
+    type Item = (Value, Value);
+
+    fn next(&mut self) -> Option<(Value, Value)> { self.entries.next() }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.entries.size_hint() }
+}
+
+impl IntoIterator for RHash {
+    type Item = (Value, Value);
+    type IntoIter = Iter;
+
+    fn into_iter(self) -> Iter { self.iter() }
+}
+
+impl<K, V> FromIterator<(K, V)> for RHash where K: IntoValue, V: IntoValue {
+    fn from_iter<I>(iter: I) -> Self where I: IntoIterator<Item=(K, V)> {
+        let hash = RHash::new();
+
+        for (key, value) in iter {
+            hash.insert(key, value);
+        }
+        hash
+    }
+}
+
+impl<K, V> From<HashMap<K, V>> for RHash where K: IntoValue, V: IntoValue {
+    fn from(map: HashMap<K, V>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for RHash where K: IntoValue, V: IntoValue {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<RHash> for Value {
+    fn from(hash: RHash) -> Self {
+        hash.0
+    }
+}
+
+impl IntoValue for RHash {
+    fn into_value(self) -> Value { self.0 }
+}
+
+impl TryFromValue for RHash {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RHash::from_value(value).ok_or_else(|| ConversionError::wrong_type("Hash", value))
+    }
+}
+
+impl fmt::Debug for RHash {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, fmt)
+    }
+}
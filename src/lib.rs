@@ -6,18 +6,64 @@ pub mod value;
 pub mod builder;
 /// Classes.
 pub mod classes;
+/// Conversions between Rust and Ruby values.
+pub mod convert;
+/// Ruby exceptions.
+pub mod exception;
+/// Ruby strings.
+pub mod string;
+/// Ruby arrays.
+pub mod array;
+/// Ruby hashes.
+pub mod hash;
+/// Wrapping Rust values in Ruby objects.
+pub mod typed_data;
+/// Keeping Ruby values alive.
+pub mod gc;
+/// Ruby blocks.
+pub mod block;
+/// Parsing method arguments.
+pub mod args;
+/// Exposing Rust types as Ruby classes.
+pub mod derive;
+/// Converting Rust values to and from Ruby values with serde.
+#[cfg(feature = "serde")]
+pub mod serialization;
+/// Running Ruby from multi-threaded programs.
+pub mod executor;
 
 /// FFI Utilitity methods.
 pub mod util;
 
+#[cfg(feature = "num-bigint")]
+mod bigint;
+mod callback;
+mod ffi;
+mod global;
+
 #[cfg(test)]
 mod test;
 
-pub use self::vm::{VM,ErrorKind};
+pub use self::vm::{VM,VMBuilder,ErrorKind,EvalOptions,OwnedError};
 pub use self::value::Value;
+pub use self::string::RString;
+pub use self::array::RArray;
+pub use self::hash::RHash;
+pub use self::args::{ArgSpec, Args};
+pub use self::convert::{ConversionError, IntoValue, TryFromValue};
+pub use self::exception::{ExceptionDetails, RubyError, RubyException};
+pub use self::typed_data::TypedData;
+pub use self::gc::RootedValue;
+pub use self::executor::{ExecutorHandle, VmExecutor};
+pub use self::derive::{RubyClass, RubyMethods};
+#[cfg(feature = "derive")]
+pub use rurust_derive::{ruby_methods, RubyClass};
+#[cfg(feature = "serde")]
+pub use self::serialization::{from_value, to_value, to_value_with, KeyStyle};
 
+// Lets the derive macros refer to `::rurust` inside this crate too.
+extern crate self as rurust;
 extern crate libc;
-extern crate mri_sys as ffi;
 #[macro_use]
 extern crate lazy_static;
 
@@ -0,0 +1,210 @@
+//! Deserializing Rust values out of Ruby values.
+
+use crate::{ConversionError, RArray, RHash, RString, Value};
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// A serde deserializer reading a Ruby value.
+#[derive(Copy, Clone, Debug)]
+pub struct Deserializer {
+    value: Value,
+}
+
+/// Reads the elements of an `Array`.
+struct SeqAccess(crate::array::Iter);
+
+/// Reads the entries of a `Hash`.
+struct MapAccess {
+    entries: crate::hash::Iter,
+    value: Option<Value>,
+}
+
+/// Reads an enum, either a variant name or a single-entry `Hash`.
+struct EnumAccess {
+    variant: Value,
+    data: Option<Value>,
+}
+
+impl Deserializer {
+    /// Creates a deserializer reading a value.
+    pub fn new(value: Value) -> Self {
+        Deserializer { value }
+    }
+}
+
+/// Gets the name of a `String` or `Symbol`.
+fn name(value: Value) -> Option<String> {
+    if value.is_symbol() {
+        Some(value.display_string())
+    } else {
+        RString::from_value(value).and_then(|s| s.try_to_string())
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = ConversionError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        let value = self.value;
+
+        if value.is_nil() {
+            visitor.visit_unit()
+        } else if value.is_true() || value.is_false() {
+            visitor.visit_bool(value.is_true())
+        } else if value.is_integer() {
+            if let Ok(v) = value.try_convert::<i64>() {
+                visitor.visit_i64(v)
+            } else if let Ok(v) = value.try_convert::<u64>() {
+                visitor.visit_u64(v)
+            } else if let Ok(v) = value.try_convert::<i128>() {
+                visitor.visit_i128(v)
+            } else {
+                visitor.visit_u128(value.try_convert()?)
+            }
+        } else if value.is_float() {
+            visitor.visit_f64(value.to_f64())
+        } else if value.is_symbol() {
+            visitor.visit_string(value.display_string())
+        } else if let Some(string) = RString::from_value(value) {
+            match string.try_to_string() {
+                Some(s) => visitor.visit_string(s),
+                None => visitor.visit_byte_buf(string.to_bytes()),
+            }
+        } else if let Some(array) = RArray::from_value(value) {
+            visitor.visit_seq(SeqAccess(array.iter()))
+        } else if let Some(hash) = RHash::from_value(value) {
+            visitor.visit_map(MapAccess { entries: hash.iter(), value: None })
+        } else {
+            Err(ConversionError::wrong_type("a serializable value", value))
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        if self.value.is_nil() { visitor.visit_none() } else { visitor.visit_some(self) }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        match RString::from_value(self.value) {
+            Some(string) => visitor.visit_byte_buf(string.to_bytes()),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V)
+        -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        if name(self.value).is_some() {
+            return visitor.visit_enum(EnumAccess { variant: self.value, data: None });
+        }
+
+        match RHash::from_value(self.value).map(|hash| (hash.len(), hash.iter().next())) {
+            Some((1, Some((variant, data)))) => visitor.visit_enum(EnumAccess { variant, data: Some(data) }),
+            _ => Err(ConversionError::wrong_type("a variant name or single-entry Hash", self.value)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = ConversionError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ConversionError>
+        where T: DeserializeSeed<'de> {
+        match self.0.next() {
+            Some(element) => seed.deserialize(Deserializer::new(element)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = ConversionError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, ConversionError>
+        where K: DeserializeSeed<'de> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer::new(key)).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, ConversionError>
+        where V: DeserializeSeed<'de> {
+        let value = self.value.take()
+            .ok_or_else(|| ConversionError::Custom("map value deserialized before its key".to_owned()))?;
+
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.entries.size_hint().1
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = ConversionError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), ConversionError>
+        where V: DeserializeSeed<'de> {
+        let variant = name(self.variant)
+            .ok_or_else(|| ConversionError::wrong_type("a variant name", self.variant))?;
+
+        let variant = seed.deserialize(variant.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess {
+    type Error = ConversionError;
+
+    fn unit_variant(self) -> Result<(), ConversionError> {
+        match self.data {
+            None => Ok(()),
+            Some(data) if data.is_nil() => Ok(()),
+            Some(data) => Err(ConversionError::wrong_type("NilClass", data)),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, ConversionError>
+        where T: DeserializeSeed<'de> {
+        seed.deserialize(Deserializer::new(self.data.unwrap_or_else(Value::nil)))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        de::Deserializer::deserialize_seq(Deserializer::new(self.data.unwrap_or_else(Value::nil)), visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, ConversionError>
+        where V: Visitor<'de> {
+        de::Deserializer::deserialize_map(Deserializer::new(self.data.unwrap_or_else(Value::nil)), visitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ConversionError> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self { self }
+}
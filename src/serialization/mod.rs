@@ -0,0 +1,60 @@
+//! Converting Rust values to and from Ruby values with serde.
+//!
+//! Structs become `Hash`es keyed by field name, sequences and tuples
+//! become `Array`s, and enums are externally tagged, so a variant with
+//! data becomes a single-entry `Hash` from the variant name to the data.
+//! Unit variants become just the variant name.
+//!
+//! Deserialization accepts both `Symbol` and `String` keys.
+
+mod de;
+mod ser;
+
+use crate::{ConversionError, Value};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::fmt;
+
+pub use self::de::Deserializer;
+pub use self::ser::Serializer;
+
+/// How field and variant names are represented in Ruby.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyStyle {
+    /// As `Symbol`s, like `{ name: "rurust" }`.
+    Symbol,
+    /// As `String`s, like `{ "name" => "rurust" }`.
+    String,
+}
+
+/// Converts a Rust value into a Ruby value, with `Symbol` keys.
+pub fn to_value<T>(value: &T) -> Result<Value, ConversionError>
+    where T: Serialize + ?Sized {
+    to_value_with(value, KeyStyle::Symbol)
+}
+
+/// Converts a Rust value into a Ruby value, with the given style of keys.
+pub fn to_value_with<T>(value: &T, keys: KeyStyle) -> Result<Value, ConversionError>
+    where T: Serialize + ?Sized {
+    value.serialize(Serializer::new(keys))
+}
+
+/// Converts a Ruby value into a Rust value.
+pub fn from_value<T>(value: Value) -> Result<T, ConversionError>
+    where T: DeserializeOwned {
+    T::deserialize(Deserializer::new(value))
+}
+
+impl serde::ser::Error for ConversionError {
+    fn custom<T>(message: T) -> Self where T: fmt::Display {
+        ConversionError::Custom(message.to_string())
+    }
+}
+
+impl serde::de::Error for ConversionError {
+    fn custom<T>(message: T) -> Self where T: fmt::Display {
+        ConversionError::Custom(message.to_string())
+    }
+}
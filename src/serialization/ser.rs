@@ -0,0 +1,272 @@
+//! Serializing Rust values into Ruby values.
+
+use super::KeyStyle;
+use crate::{ConversionError, IntoValue, RArray, RHash, Value};
+
+use serde::ser::{self, Serialize};
+
+/// A serde serializer producing Ruby values.
+#[derive(Copy, Clone, Debug)]
+pub struct Serializer {
+    keys: KeyStyle,
+}
+
+/// Builds an `Array`, used for sequences, tuples and tuple variants.
+pub struct SerializeArray {
+    serializer: Serializer,
+    array: RArray,
+    variant: Option<Value>,
+}
+
+/// Builds a `Hash`, used for maps, structs and struct variants.
+pub struct SerializeHash {
+    serializer: Serializer,
+    hash: RHash,
+    key: Option<Value>,
+    variant: Option<Value>,
+}
+
+impl Serializer {
+    /// Creates a serializer using the given style of keys.
+    pub fn new(keys: KeyStyle) -> Self {
+        Serializer { keys }
+    }
+
+    /// Creates a key for a field or variant name.
+    fn key(&self, name: &str) -> Value {
+        match self.keys {
+            KeyStyle::Symbol => Value::symbol(name),
+            KeyStyle::String => Value::string(name),
+        }
+    }
+
+    /// Wraps the data of an enum variant in a single-entry hash.
+    fn tagged(&self, variant: Value, data: Value) -> Value {
+        let hash = RHash::new();
+        hash.insert(variant, data);
+        hash.value()
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeHash;
+    type SerializeStruct = SerializeHash;
+    type SerializeStructVariant = SerializeHash;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_i8(self, v: i8) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_i16(self, v: i16) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_i32(self, v: i32) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_i64(self, v: i64) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_i128(self, v: i128) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_u8(self, v: u8) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_u16(self, v: u16) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_u32(self, v: u32) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_u64(self, v: u64) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_u128(self, v: u128) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_f32(self, v: f32) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+    fn serialize_f64(self, v: f64) -> Result<Value, ConversionError> { Ok(v.into_value()) }
+
+    fn serialize_char(self, v: char) -> Result<Value, ConversionError> {
+        Ok(Value::string(v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ConversionError> { Ok(Value::string(v)) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ConversionError> { Ok(Value::bytes(v)) }
+
+    fn serialize_none(self) -> Result<Value, ConversionError> { Ok(Value::nil()) }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, ConversionError>
+        where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, ConversionError> { Ok(Value::nil()) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ConversionError> { Ok(Value::nil()) }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str)
+        -> Result<Value, ConversionError> {
+        Ok(self.key(variant))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value, ConversionError>
+        where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _index: u32, variant: &'static str,
+                                    value: &T) -> Result<Value, ConversionError>
+        where T: Serialize + ?Sized {
+        let data = value.serialize(self)?;
+        Ok(self.tagged(self.key(variant), data))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, ConversionError> {
+        Ok(SerializeArray {
+            serializer: self,
+            array: RArray::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, ConversionError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray, ConversionError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize)
+        -> Result<SerializeArray, ConversionError> {
+        let mut array = self.serialize_seq(Some(len))?;
+        array.variant = Some(self.key(variant));
+        Ok(array)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeHash, ConversionError> {
+        Ok(SerializeHash { serializer: self, hash: RHash::new(), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeHash, ConversionError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize)
+        -> Result<SerializeHash, ConversionError> {
+        let mut hash = self.serialize_map(Some(len))?;
+        hash.variant = Some(self.key(variant));
+        Ok(hash)
+    }
+}
+
+impl SerializeArray {
+    fn push<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.array.push(value.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConversionError> {
+        match self.variant {
+            Some(variant) => Ok(self.serializer.tagged(variant, self.array.value())),
+            None => Ok(self.array.value()),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl SerializeHash {
+    fn field<T>(&mut self, name: &'static str, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.hash.insert(self.serializer.key(name), value.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConversionError> {
+        match self.variant {
+            Some(variant) => Ok(self.serializer.tagged(variant, self.hash.value())),
+            None => Ok(self.hash.value()),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeHash {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.key = Some(key.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        let key = self.key.take()
+            .ok_or_else(|| ConversionError::Custom("map value serialized before its key".to_owned()))?;
+
+        self.hash.insert(key, value.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl ser::SerializeStruct for SerializeHash {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for SerializeHash {
+    type Ok = Value;
+    type Error = ConversionError;
+
+    fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), ConversionError>
+        where T: Serialize + ?Sized {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Value, ConversionError> { self.finish() }
+}
@@ -0,0 +1,144 @@
+//! Ruby strings.
+
+use crate::{classes, ffi, libc, ConversionError, ErrorKind, IntoValue, RubyException, TryFromValue, Value};
+
+use std::{fmt, slice};
+
+/// A Ruby `String`.
+///
+/// Like `Value`, this is a plain reference that does not keep the string alive.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct RString(Value);
+
+impl RString {
+    /// Creates a UTF-8 string.
+    pub fn new(s: &str) -> Self {
+        RString(Value::from(unsafe {
+            ffi::rb_utf8_str_new(s.as_ptr() as *const libc::c_char, s.len() as libc::c_long)
+        }))
+    }
+
+    /// Creates a binary (`ASCII-8BIT`) string.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        RString(Value::from(unsafe {
+            ffi::rb_str_new(bytes.as_ptr() as *const libc::c_char, bytes.len() as libc::c_long)
+        }))
+    }
+
+    /// Creates a string in the encoding with the given name, such as `Shift_JIS`.
+    ///
+    /// The bytes are not checked against the encoding.
+    pub fn with_encoding(bytes: &[u8], encoding: &str) -> Result<Self, ErrorKind> {
+        let name = std::ffi::CString::new(encoding)
+            .map_err(|_| ErrorKind::VM("encoding names cannot contain NUL".to_owned()))?;
+        let index = unsafe { ffi::rb_enc_find_index(name.as_ptr()) };
+
+        if index < 0 {
+            let message = format!("unknown encoding name - {}", encoding);
+            return Err(RubyException::new(classes::ArgError(), message).into());
+        }
+
+        Ok(RString(Value::from(unsafe {
+            ffi::rb_enc_str_new(bytes.as_ptr() as *const libc::c_char, bytes.len() as libc::c_long,
+                                ffi::rb_enc_from_index(index))
+        })))
+    }
+
+    /// Creates a frozen, deduplicated string, like a string literal
+    /// with `# frozen_string_literal: true`.
+    pub fn frozen(s: &str) -> Self {
+        RString(Value::from(unsafe { ffi::rb_str_to_interned_str(RString::new(s).0 .0) }))
+    }
+
+    /// Views a value as a string.
+    ///
+    /// Returns `None` if the value is not a `String`.
+    pub fn from_value(value: Value) -> Option<Self> {
+        if value.is_string() { Some(RString(value)) } else { None }
+    }
+
+    /// Gets the underlying string object.
+    pub fn value(&self) -> Value { self.0 }
+
+    /// Gets the length in bytes.
+    pub fn len(&self) -> usize {
+        unsafe { ffi::RSTRING_LEN(self.0 .0) as usize }
+    }
+
+    /// Checks if the string is empty.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Borrows the bytes of the string.
+    ///
+    /// # Safety
+    ///
+    /// The slice points into the Ruby object. It is invalidated if the
+    /// string is modified or garbage collected whilst it is in use.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        let len = self.len();
+        if len == 0 {
+            return &[];
+        }
+
+        let mut value = self.0 .0;
+        let ptr = ffi::rb_string_value_ptr(&mut value);
+        slice::from_raw_parts(ptr as *const u8, len)
+    }
+
+    /// Copies the bytes of the string.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        unsafe { self.as_bytes().to_vec() }
+    }
+
+    /// Gets the name of the string's encoding, such as `UTF-8`.
+    pub fn encoding(&self) -> String {
+        unsafe {
+            let name = ffi::rb_enc_name(ffi::rb_enc_get(self.0 .0));
+            std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Checks if the string is frozen.
+    pub fn is_frozen(&self) -> bool {
+        Value::from(unsafe { ffi::rb_obj_frozen_p(self.0 .0) }).is_true()
+    }
+
+    /// Copies the string into a Rust `String`.
+    ///
+    /// Returns `None` if the bytes are not valid UTF-8.
+    pub fn try_to_string(&self) -> Option<String> {
+        String::from_utf8(self.to_bytes()).ok()
+    }
+
+    /// Copies the string into a Rust `String`, replacing any bytes that
+    /// are not valid UTF-8 with `U+FFFD`.
+    ///
+    /// The bytes are not transcoded, so strings in other encodings
+    /// should be converted with `String#encode` first.
+    pub fn to_str_lossy(&self) -> String {
+        String::from_utf8_lossy(unsafe { self.as_bytes() }).into_owned()
+    }
+}
+
+impl From<RString> for Value {
+    fn from(string: RString) -> Self {
+        string.0
+    }
+}
+
+impl IntoValue for RString {
+    fn into_value(self) -> Value { self.0 }
+}
+
+impl TryFromValue for RString {
+    fn try_from_value(value: Value) -> Result<Self, ConversionError> {
+        RString::from_value(value).ok_or_else(|| ConversionError::wrong_type("String", value))
+    }
+}
+
+impl fmt::Debug for RString {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, fmt)
+    }
+}
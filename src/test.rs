@@ -1,13 +1,43 @@
-use crate::{classes, ErrorKind, VM};
+use crate::{block, classes, ArgSpec, typed_data, ConversionError, ErrorKind, EvalOptions, IntoValue, OwnedError, RArray, RHash, RString, RubyError, RubyException, TypedData, Value, VM};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
 
 // We cannot have more than two VMs at a time, and so we have a single
 // test that calls the other tests.
 #[test]
 fn tests() {
-    let mut vm = VM::new().unwrap();
+    let mut vm = VM::builder().load_path("/opt/rurust-test").build().unwrap();
 
     self::returns_syntax_errors_as_results(&mut vm);
     self::can_eval_simple_assignment(&mut vm);
+    self::can_call_closure_methods(&mut vm);
+    self::can_convert_values(&mut vm);
+    self::raises_panics_as_exceptions(&mut vm);
+    self::can_protect_raising_calls(&mut vm);
+    self::exposes_exception_details(&mut vm);
+    self::can_raise_typed_exceptions(&mut vm);
+    self::can_map_rust_errors_to_exceptions(&mut vm);
+    self::can_wrap_rust_structs(&mut vm);
+    self::rooted_values_survive_gc(&mut vm);
+    self::can_define_hooked_globals(&mut vm);
+    self::can_eval_with_options(&mut vm);
+    self::can_load_and_run_scripts(&mut vm);
+    self::applies_vm_options(&mut vm);
+    self::can_release_the_gvl(&mut vm);
+    self::can_use_blocks(&mut vm);
+    self::can_create_procs_from_closures(&mut vm);
+    self::can_use_arrays(&mut vm);
+    self::can_use_hashes(&mut vm);
+    self::can_create_strings(&mut vm);
+    self::can_convert_big_integers(&mut vm);
+    self::can_parse_argument_specs(&mut vm);
+    self::can_control_method_visibility(&mut vm);
+    #[cfg(feature = "serde")]
+    self::can_serialize_with_serde(&mut vm);
+    #[cfg(feature = "derive")]
+    self::can_derive_ruby_classes(&mut vm);
 }
 
 fn returns_syntax_errors_as_results(vm: &mut VM) {
@@ -25,3 +55,763 @@ fn can_eval_simple_assignment(vm: &mut VM) {
     vm.eval("a = 1").unwrap();
 }
 
+
+fn can_call_closure_methods(vm: &mut VM) {
+    let offset = 10;
+
+    vm.class("ClosureMethods")
+        .method_fn("add_offset", move |_, args| Ok(Value::integer(args[0].to_i64() + offset)))
+        .singleton_method_fn("answer", |_, _| Ok(Value::integer(42)))
+        .build();
+
+    assert_eq!(15, vm.eval("ClosureMethods.new.add_offset(5)").unwrap().to_i64());
+    assert_eq!(42, vm.eval("ClosureMethods.answer").unwrap().to_i64());
+
+    // Methods are found through aliases, subclasses and copies of the class.
+    assert_eq!(16, vm.eval("class ClosureMethods; alias plus add_offset; end; ClosureMethods.new.plus(6)")
+        .unwrap().to_i64());
+    assert_eq!(17, vm.eval("Class.new(ClosureMethods).new.add_offset(7)").unwrap().to_i64());
+    assert_eq!(18, vm.eval("ClosureMethods.dup.new.add_offset(8)").unwrap().to_i64());
+    assert_eq!(42, vm.eval("ClosureMethods.clone.answer").unwrap().to_i64());
+    assert_eq!(42, vm.eval("Class.new(ClosureMethods).answer").unwrap().to_i64());
+}
+
+fn can_convert_values(vm: &mut VM) {
+    let numbers: Vec<i32> = vm.eval("[1, 2, 3]").unwrap().try_convert().unwrap();
+    assert_eq!(vec![1, 2, 3], numbers);
+
+    let pair: (String, Option<bool>) = vm.eval("['hello', nil]").unwrap().try_convert().unwrap();
+    assert_eq!(("hello".to_owned(), None), pair);
+
+    assert_eq!(Err(ConversionError::OutOfRange { target: "u8" }),
+               vm.eval("300").unwrap().try_convert::<u8>());
+    assert!(vm.eval("'abc'").unwrap().try_convert::<i64>().is_err());
+
+    let array = vec![1.5f64, 2.5].into_value();
+    assert_eq!(2.5, array.try_convert::<Vec<f64>>().unwrap()[1]);
+}
+
+fn raises_panics_as_exceptions(vm: &mut VM) {
+    vm.module("Panicky")
+        .function_fn("explode", |_, _| panic!("kaboom"))
+        .build();
+    vm.define_global_function_fn("fail_with_error", |_, _| Err(ErrorKind::VM("nope".to_owned())));
+
+    match vm.eval("Panicky.explode").unwrap_err() {
+        ErrorKind::Exception(e) => {
+            assert_eq!(classes::RuntimeError(), e.class());
+            assert_eq!("kaboom", e.message());
+        },
+        _ => panic!("unexpected exception type"),
+    }
+
+    assert!(vm.eval("fail_with_error").is_err());
+
+    // An anonymous class is only kept alive by being set as the panic class.
+    let panic_class = vm.eval("Class.new(StandardError)").unwrap();
+    vm.set_panic_class(panic_class);
+    vm.eval("GC.start").unwrap();
+    match vm.eval("Panicky.explode").unwrap_err() {
+        ErrorKind::Exception(e) => assert!(e.is_kind_of(classes::StandardError())),
+        _ => panic!("unexpected exception type"),
+    }
+
+    // Falls back to `RuntimeError` if the panic class cannot be created.
+    let failing_class = vm.eval("Class.new(StandardError) { def initialize(*); raise 'nope'; end }").unwrap();
+    vm.set_panic_class(failing_class);
+    match vm.eval("Panicky.explode").unwrap_err() {
+        ErrorKind::Exception(e) => {
+            assert_eq!(classes::RuntimeError(), e.class());
+            assert_eq!("kaboom", e.message());
+        },
+        _ => panic!("unexpected exception type"),
+    }
+    vm.set_panic_class(classes::RuntimeError());
+}
+
+fn can_protect_raising_calls(vm: &mut VM) {
+    let object = vm.eval("Object.new").unwrap();
+
+    match object.try_send("no_such_method", &[]).unwrap_err() {
+        ErrorKind::Exception(e) => assert_eq!(classes::NoMethodError(), e.class()),
+        _ => panic!("unexpected exception type"),
+    }
+
+    assert!(object.try_to_i64().is_err());
+    assert_eq!(3, Value::integer(3).try_to_i64().unwrap());
+    assert!(vm.require("this_file_does_not_exist").is_err());
+    assert_eq!(Ok(7), VM::protect(|| 7));
+}
+
+fn exposes_exception_details(vm: &mut VM) {
+    let code = "begin; raise ArgumentError, 'inner'; rescue; raise KeyError, 'outer'; end";
+
+    match vm.eval(code).unwrap_err() {
+        ErrorKind::Exception(e) => {
+            assert_eq!("KeyError", e.class_path());
+            assert_eq!("outer", e.message());
+            assert_eq!("ArgumentError", e.cause().unwrap().class_path());
+            assert_eq!(1, e.causes().len());
+            assert!(!e.backtrace().is_empty());
+            assert!(e.full_message().contains("outer"));
+        },
+        _ => panic!("unexpected exception type"),
+    }
+
+    // The details are captured up front, so they can be reported from any thread.
+    let error = vm.eval("begin; raise IOError, 'inner'; rescue; raise 'reported elsewhere'; end").unwrap_err();
+    let error = OwnedError::from(error);
+    let report = std::thread::spawn(move || match error {
+        OwnedError::Exception(e) => (e.to_string(), e.is_kind_of_path("StandardError"),
+                                     e.cause().map(|cause| cause.to_string())),
+        _ => panic!("unexpected error type"),
+    }).join().unwrap();
+    assert_eq!(("RuntimeError: reported elsewhere".to_owned(), true, Some("IOError: inner".to_owned())), report);
+
+    // Exceptions raised whilst capturing the details are not captured in turn.
+    match vm.eval("class LoopError < StandardError; def message; raise LoopError; end; end; raise LoopError")
+        .unwrap_err() {
+        ErrorKind::Exception(e) => assert_eq!(("LoopError".to_owned(), String::new()), (e.class_path(), e.message())),
+        _ => panic!("unexpected exception type"),
+    }
+}
+
+fn can_raise_typed_exceptions(vm: &mut VM) {
+    vm.class("Raiser")
+        .singleton_method_fn("raise_it", |_, _| {
+            let cause = RubyException::new(classes::IOError(), "disk on fire");
+
+            Err(RubyException::new(classes::ArgError(), "100% %s wrong")
+                .with_cause(cause)
+                .with_ivar("@code", Value::integer(7))
+                .into())
+        })
+        .build();
+
+    match vm.eval("Raiser.raise_it").unwrap_err() {
+        ErrorKind::Exception(e) => {
+            assert_eq!(classes::ArgError(), e.class());
+            assert_eq!("100% %s wrong", e.message());
+            assert_eq!("IOError", e.cause().unwrap().class_path());
+            assert_eq!(7, e.value().get_ivar("@code").to_i64());
+        },
+        _ => panic!("unexpected exception type"),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum LibError {
+    Timeout { seconds: i64 },
+    Other(String),
+}
+
+impl fmt::Display for LibError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LibError::Timeout { seconds } => write!(fmt, "timed out after {}s", seconds),
+            LibError::Other(ref message) => write!(fmt, "{}", message),
+        }
+    }
+}
+
+impl RubyError for LibError {
+    fn class_path(&self) -> &'static str {
+        match *self {
+            LibError::Timeout { .. } => "MyLib::TimeoutError",
+            LibError::Other(..) => "MyLib::Error",
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        match *self {
+            LibError::Timeout { seconds } => vec![("seconds", Value::integer(seconds))],
+            LibError::Other(..) => Vec::new(),
+        }
+    }
+
+    fn from_exception(exception: &RubyException) -> Option<Self> {
+        if exception.is_kind_of_path("MyLib::TimeoutError") {
+            Some(LibError::Timeout { seconds: exception.field("seconds").to_i64() })
+        } else if exception.is_kind_of_path("MyLib::Error") {
+            Some(LibError::Other(exception.message()))
+        } else {
+            None
+        }
+    }
+}
+
+fn can_map_rust_errors_to_exceptions(vm: &mut VM) {
+    let my_lib = vm.module("MyLib").build();
+    let error = my_lib.nested_exception_class("Error").build();
+    my_lib.nested_exception_class("TimeoutError").extend(error).field("seconds").build();
+
+    vm.define_global_function_fn("time_out", |_, _| Err(LibError::Timeout { seconds: 5 }.into_error()));
+
+    assert_eq!(5, vm.eval("begin; time_out; rescue MyLib::Error => e; e.seconds; end").unwrap().to_i64());
+    assert_eq!(Some(LibError::Timeout { seconds: 5 }), vm.eval("time_out").unwrap_err().downcast());
+    assert_eq!(Some(LibError::Other("boom".to_owned())),
+               vm.eval("raise MyLib::Error, 'boom'").unwrap_err().downcast());
+    assert_eq!(None, vm.eval("raise 'boom'").unwrap_err().downcast::<LibError>());
+}
+
+struct Counter {
+    count: i64,
+}
+
+impl TypedData for Counter { }
+
+fn can_wrap_rust_structs(vm: &mut VM) {
+    let class = vm.class("Counter")
+        .typed_data::<Counter>()
+        .method_fn("initialize", |this, args| {
+            typed_data::set(this, Counter { count: args[0].to_i64() })?;
+            Ok(Value::nil())
+        })
+        .method_fn("increment", |this, _| {
+            let mut counter = this.downcast_mut::<Counter>().expect("not a counter");
+            counter.count += 1;
+            Ok(Value::integer(counter.count))
+        })
+        .build();
+
+    assert_eq!(6, vm.eval("c = Counter.new(4); c.increment; c.increment").unwrap().to_i64());
+
+    let counter = typed_data::wrap(class, Counter { count: 10 });
+    assert_eq!(10, counter.downcast_ref::<Counter>().unwrap().count);
+    assert!(Value::integer(1).downcast_ref::<Counter>().is_none());
+    assert!(vm.eval("Counter.allocate").unwrap().downcast_ref::<Counter>().is_none());
+
+    struct Tracked(Rc<Cell<u32>>);
+
+    impl TypedData for Tracked {
+        fn free(self) { self.0.set(self.0.get() + 1); }
+    }
+
+    let freed = Rc::new(Cell::new(0));
+    let tracked = typed_data::wrap(class, Tracked(freed.clone()));
+    typed_data::set(tracked, Tracked(freed.clone())).unwrap();
+    assert_eq!(1, freed.get());
+
+    let _borrow = counter.downcast_mut::<Counter>().unwrap();
+    assert_eq!(Err(ConversionError::AlreadyBorrowed), typed_data::set(counter, Counter { count: 0 }));
+}
+
+fn rooted_values_survive_gc(vm: &mut VM) {
+    // Kept on the heap, where the garbage collector cannot see it.
+    let rooted = Box::new(vm.eval("'still ' + 'here'").unwrap().root());
+    vm.eval("GC.start").unwrap();
+
+    assert_eq!(Some("still here".to_owned()), rooted.as_string());
+}
+
+fn can_define_hooked_globals(vm: &mut VM) {
+    let request_id = Rc::new(Cell::new(1));
+    let (get_id, set_id) = (request_id.clone(), request_id.clone());
+
+    vm.define_virtual_global("$request_id",
+        move || Ok(Value::integer(get_id.get())),
+        move |value| { set_id.set(value.to_i64()); Ok(()) });
+    vm.define_readonly_global("$answer", || Ok(Value::integer(42)));
+    vm.define_hooked_global("$doubled", Value::integer(1),
+        Ok,
+        |value| Ok(Value::integer(value.to_i64() * 2)));
+
+    assert_eq!(1, vm.eval("$request_id").unwrap().to_i64());
+    vm.eval("$request_id = 5").unwrap();
+    assert_eq!(5, request_id.get());
+
+    assert_eq!(42, vm.eval("$answer").unwrap().to_i64());
+    assert!(vm.eval("$answer = 1").is_err());
+
+    assert_eq!(8, vm.eval("$doubled = 4; $doubled").unwrap().to_i64());
+
+    // The closures and stored values are kept alive by the variables.
+    vm.define_hooked_global("$greeting", Value::string("hello"), Ok, Ok);
+    vm.eval("GC.start").unwrap();
+    assert_eq!(5, vm.eval("$request_id").unwrap().to_i64());
+    assert_eq!(Some("hello".to_owned()), vm.eval("$greeting").unwrap().as_string());
+}
+
+fn can_eval_with_options(vm: &mut VM) {
+    let options = EvalOptions {
+        filename: Some("plugin.rb".to_owned()),
+        line: Some(10),
+        ..EvalOptions::default()
+    };
+
+    match vm.eval_with("\nraise 'oops'", options).unwrap_err() {
+        ErrorKind::Exception(e) => {
+            let frame = &e.backtrace()[0];
+            assert_eq!("plugin.rb", frame.file);
+            assert_eq!(Some(11), frame.line);
+        },
+        _ => panic!("unexpected exception type"),
+    }
+
+    let binding = vm.eval("x = 3; binding").unwrap();
+    let options = EvalOptions { binding: Some(binding), ..EvalOptions::default() };
+    assert_eq!(6, vm.eval_with("x * 2", options).unwrap().to_i64());
+
+    let receiver = vm.eval("'receiver'").unwrap();
+    let options = EvalOptions { self_object: Some(receiver), ..EvalOptions::default() };
+    assert_eq!(Some("RECEIVER".to_owned()), vm.eval_with("upcase", options).unwrap().as_string());
+}
+
+fn can_load_and_run_scripts(vm: &mut VM) {
+    let dir = std::env::temp_dir();
+    let library = dir.join("rurust_test_library.rb");
+    let script = dir.join("rurust_test_script.rb");
+    let failing = dir.join("rurust_test_failing.rb");
+
+    std::fs::write(&library, "$loaded = ($loaded || 0) + 1").unwrap();
+    std::fs::write(&script, "$script_args = ARGV.dup; $script_file = __FILE__; exit").unwrap();
+    std::fs::write(&failing, "exit 3").unwrap();
+
+    vm.load_file(&library, false).unwrap();
+    vm.load_file(&library, true).unwrap();
+    assert_eq!(2, vm.eval("$loaded").unwrap().to_i64());
+
+    vm.run_script(&script, &["--verbose", "input.txt"]).unwrap();
+    assert_eq!(vec!["--verbose".to_owned(), "input.txt".to_owned()],
+               vm.eval("$script_args").unwrap().try_convert::<Vec<String>>().unwrap());
+    assert_eq!(Some(script.to_string_lossy().into_owned()), vm.eval("$script_file").unwrap().as_string());
+
+    assert!(vm.run_script(&failing, &[] as &[&str]).is_err());
+    assert!(vm.load_file(dir.join("rurust_no_such_file.rb"), false).is_err());
+}
+
+fn applies_vm_options(vm: &mut VM) {
+    assert!(vm.eval("$LOAD_PATH.include?('/opt/rurust-test')").unwrap().is_true());
+    vm.require("set").unwrap();
+}
+
+fn can_release_the_gvl(vm: &mut VM) {
+    unsafe {
+        vm.class("Crunch")
+            .method_fn_without_gvl("sum", |(a, b): (i64, i64)| Ok(a + b))
+            .build();
+    }
+
+    assert_eq!(5, vm.eval("Crunch.new.sum(2, 3)").unwrap().to_i64());
+    assert!(vm.eval("Crunch.new.sum('2', 3)").is_err());
+    assert_eq!(4, unsafe { VM::without_gvl(|| 2 + 2) }.unwrap());
+    assert_eq!(9, unsafe { VM::without_gvl_interruptible(|| 3 * 3, || ()) }.unwrap());
+}
+
+fn can_use_blocks(vm: &mut VM) {
+    vm.class("Triple")
+        .method_fn("each", |_, _| {
+            for i in 1..=3 {
+                block::yield_value(&[Value::integer(i)])?;
+            }
+            Ok(Value::nil())
+        })
+        .method_fn("block_given?", |_, _| Ok(Value::boolean(block::block_given())))
+        .method_fn("block", |_, _| Ok(block::block_proc().unwrap_or_else(Value::nil)))
+        .build();
+
+    assert_eq!(6, vm.eval("t = 0; Triple.new.each { |i| t += i }; t").unwrap().to_i64());
+    assert_eq!(2, vm.eval("Triple.new.each { |i| break i * 2 }").unwrap().to_i64());
+    assert!(vm.eval("Triple.new.each").is_err());
+    assert!(vm.eval("Triple.new.block_given? { }").unwrap().is_true());
+    assert!(vm.eval("Triple.new.block_given?").unwrap().is_false());
+    assert_eq!(7, vm.eval("Triple.new.block { 7 }.call").unwrap().to_i64());
+
+    let mut seen = Vec::new();
+    let array = vm.eval("[1, 2, 3]").unwrap();
+    let doubled = array.send_with_block("map", &[], |args| {
+        seen.push(args[0].to_i64());
+        Ok(Value::integer(args[0].to_i64() * 2))
+    }).unwrap();
+
+    assert_eq!(vec![1, 2, 3], seen);
+    assert_eq!(vec![2, 4, 6], doubled.try_convert::<Vec<i64>>().unwrap());
+}
+
+fn can_create_procs_from_closures(vm: &mut VM) {
+    let offset = 100;
+    let adder = Value::proc_from(move |args| Ok(Value::integer(args.len() as i64 + offset)));
+    let double = Value::lambda_from(1, |args| Ok(Value::integer(args[0].to_i64() * 2)));
+
+    vm.set_global("$adder", adder);
+    vm.set_global("$double", double);
+
+    assert_eq!(102, adder.call_proc(&[Value::nil(), Value::nil()]).unwrap().to_i64());
+    assert_eq!(8, double.call_proc(&[Value::integer(4)]).unwrap().to_i64());
+    assert!(vm.eval("$double.lambda?").unwrap().is_true());
+    assert_eq!(1, vm.eval("$double.arity").unwrap().to_i64());
+    assert!(double.call_proc(&[]).is_err());
+    assert_eq!(vec![2, 4], vm.eval("[1, 2].map(&$double)").unwrap().try_convert::<Vec<i64>>().unwrap());
+
+    let ruby_proc = vm.eval("proc { |a, b| a - b }").unwrap();
+    assert_eq!(3, ruby_proc.call_proc(&[Value::integer(5), Value::integer(2)]).unwrap().to_i64());
+}
+
+fn can_use_arrays(vm: &mut VM) {
+    let array = RArray::with_capacity(2);
+    array.push(1);
+    array.push("two");
+    array.set(3, 4.5);
+
+    assert_eq!(4, array.len());
+    assert_eq!(Some(1), array.get(0).map(|v| v.to_i64()));
+    assert!(array.get(2).unwrap().is_nil());
+    assert_eq!(None, array.get(4));
+    assert_eq!(Some(4.5), array.pop().map(|v| v.to_f64()));
+    assert_eq!(3, array.iter().count());
+
+    vm.set_global("$array", array.value());
+    assert_eq!("[1, \"two\", nil]", vm.eval("$array.inspect").unwrap().as_string().unwrap());
+
+    let squares: RArray = (1..=3).map(|i| i * i).collect();
+    assert_eq!(vec![1, 4, 9], squares.try_to_vec::<i64>().unwrap());
+    assert_eq!(3, Vec::<Value>::from(RArray::from(&[1u8, 2, 3][..])).len());
+
+    let empty = RArray::new();
+    assert!(empty.is_empty());
+    assert_eq!(None, empty.pop());
+    assert!(RArray::from_value(Value::integer(1)).is_none());
+
+    // Lengths are read from the array itself, for both embedded and heap arrays.
+    vm.eval("class LyingArray < Array; def length; 0; end; def size; 0; end; end").unwrap();
+    let large = RArray::from_value(vm.eval("LyingArray.new(100) { |i| i + 1 }").unwrap()).unwrap();
+    assert_eq!(100, large.len());
+    assert_eq!(5050, large.try_to_vec::<i64>().unwrap().iter().sum::<i64>());
+    assert_eq!(2, RArray::from_value(vm.eval("LyingArray[1, 2]").unwrap()).unwrap().len());
+}
+
+fn can_use_hashes(vm: &mut VM) {
+    let hash = RHash::new();
+    hash.insert("name", "rurust");
+    hash.insert("missing", Value::nil());
+    hash.insert(Value::symbol("answer"), 42);
+
+    assert_eq!(3, hash.len());
+    assert_eq!(Some("rurust".to_owned()), hash.get("name").and_then(|v| v.as_string()));
+    assert!(hash.get("missing").unwrap().is_nil());
+    assert_eq!(None, hash.get("nope"));
+    assert!(hash.contains_key(Value::symbol("answer")));
+    assert_eq!(vec!["name".to_owned(), "missing".to_owned()],
+               hash.keys().iter().take(2).map(|k| k.as_string().unwrap()).collect::<Vec<_>>());
+
+    assert!(hash.remove("missing").unwrap().is_nil());
+    assert_eq!(None, hash.remove("missing"));
+    assert_eq!(2, hash.iter().count());
+
+    hash.set_default(0);
+    assert_eq!(None, hash.get("nope"));
+    assert_eq!(0, hash.get_or_default("nope").to_i64());
+    assert_eq!(0, hash.default().to_i64());
+
+    vm.set_global("$hash", hash.value());
+    assert_eq!(42, vm.eval("$hash[:answer]").unwrap().to_i64());
+
+    let mut config = BTreeMap::new();
+    config.insert("retries".to_owned(), 3);
+    config.insert("timeout".to_owned(), 30);
+    vm.set_global("$config", RHash::from(config.clone()).value());
+    assert_eq!(33, vm.eval("$config['retries'] + $config['timeout']").unwrap().to_i64());
+
+    let round_trip: BTreeMap<String, i64> = vm.eval("$config").unwrap().try_convert().unwrap();
+    assert_eq!(config, round_trip);
+    let as_hash_map: HashMap<String, i64> = RHash::from_value(vm.eval("{ 'a' => 1 }").unwrap())
+        .unwrap().try_to_hash_map().unwrap();
+    assert_eq!(Some(&1), as_hash_map.get("a"));
+}
+
+fn can_create_strings(vm: &mut VM) {
+    let with_nul = Value::string("a\0b");
+    vm.set_global("$with_nul", with_nul);
+    assert_eq!(3, vm.eval("$with_nul.bytesize").unwrap().to_i64());
+    assert_eq!(Some("a\0b".to_owned()), with_nul.as_string());
+    assert_eq!("UTF-8", RString::from_value(with_nul).unwrap().encoding());
+
+    let binary = RString::from_value(Value::bytes(&[0xff, 0x00, 0x41])).unwrap();
+    assert_eq!("ASCII-8BIT", binary.encoding());
+    assert_eq!(vec![0xff, 0x00, 0x41], binary.to_bytes());
+    assert_eq!("\u{fffd}\0A", binary.to_str_lossy());
+    assert_eq!(None, binary.try_to_string());
+    assert!(binary.value().try_convert::<String>().is_err());
+
+    let sjis = Value::string_with_encoding(&[0x82, 0xa0], "Shift_JIS").unwrap();
+    vm.set_global("$sjis", sjis);
+    assert_eq!("あ", vm.eval("$sjis.encode('UTF-8')").unwrap().as_string().unwrap());
+    assert!(Value::string_with_encoding(b"", "NO-SUCH-ENCODING").is_err());
+
+    let frozen = RString::frozen("literal");
+    assert!(frozen.is_frozen());
+    assert!(!RString::new("literal").is_frozen());
+    vm.set_global("$frozen", Value::frozen_string("literal"));
+    assert!(vm.eval("$frozen.equal?(-'literal')").unwrap().is_true());
+
+    // Lengths, encodings and frozenness are read from the string itself.
+    vm.eval("class LyingString < String; def bytesize; 10**6; end; \
+             def encoding; raise 'encoding'; end; def frozen?; raise 'frozen?'; end; end").unwrap();
+    let short = RString::from_value(vm.eval("LyingString.new('abc')").unwrap()).unwrap();
+    assert_eq!(3, short.len());
+    assert_eq!(Some("abc".to_owned()), short.try_to_string());
+    assert_eq!("UTF-8", short.encoding());
+    assert!(!short.is_frozen());
+    let long = RString::from_value(vm.eval("LyingString.new('x' * 1000)").unwrap()).unwrap();
+    assert_eq!(1000, long.to_bytes().len());
+}
+
+fn can_convert_big_integers(vm: &mut VM) {
+    let big = Value::integer(i64::MAX);
+    vm.set_global("$big", big);
+    assert!(vm.eval("$big == 2**63 - 1").unwrap().is_true());
+    assert!(Value::integer(-5).is_integer());
+    assert_eq!(-5, Value::integer(-5).try_to_i64().unwrap());
+    assert_eq!(u64::MAX, Value::unsigned_integer(u64::MAX).try_to_u64().unwrap());
+
+    let huge = vm.eval("2**100").unwrap();
+    assert!(huge.is_integer());
+    assert_eq!(1i128 << 100, huge.try_to_i128().unwrap());
+    assert!(huge.try_to_i64().is_err());
+    assert!(Value::integer(-1).try_to_u64().is_err());
+    assert!(vm.eval("1.5").unwrap().try_to_i64().is_err());
+    assert_eq!(Err(ConversionError::OutOfRange { target: "u8" }), Value::integer(256).try_convert::<u8>());
+
+    vm.set_global("$min", i128::MIN.into_value());
+    assert!(vm.eval("$min == -2**127").unwrap().is_true());
+    assert_eq!(i128::MIN, vm.eval("$min").unwrap().try_to_i128().unwrap());
+    assert!(vm.eval("-2**127 - 1").unwrap().try_to_i128().is_err());
+    assert_eq!(u128::MAX, vm.eval("2**128 - 1").unwrap().try_convert::<u128>().unwrap());
+
+    #[cfg(feature = "num-bigint")]
+    {
+        use num_bigint::{BigInt, BigUint};
+
+        let factorial: BigInt = vm.eval("(1..30).reduce(:*)").unwrap().try_convert().unwrap();
+        assert_eq!("265252859812191058636308480000000", factorial.to_string());
+
+        vm.set_global("$negative", (-factorial).into_value());
+        assert!(vm.eval("$negative == -(1..30).reduce(:*)").unwrap().is_true());
+        assert_eq!(BigInt::from(0), Value::integer(0).try_convert::<BigInt>().unwrap());
+        assert_eq!(BigUint::from(7u8), Value::integer(7).try_convert::<BigUint>().unwrap());
+        assert!(Value::integer(-7).try_convert::<BigUint>().is_err());
+    }
+}
+
+fn can_parse_argument_specs(vm: &mut VM) {
+    let spec = ArgSpec::new()
+        .required(1)
+        .optional(1)
+        .splat()
+        .keyword("mode")
+        .keyword_default("scale", Value::integer(10))
+        .block();
+
+    vm.class("ArgSpecTest")
+        .method_with_args("describe", spec, |_, args| {
+            let parts = [
+                args.required(0).inspect_string(),
+                args.optional(0).map(|v| v.inspect_string()).unwrap_or_default(),
+                format!("{}", args.splat().len()),
+                args.keyword("mode").unwrap().inspect_string(),
+                args.keyword("scale").unwrap().inspect_string(),
+                args.block().map(|b| b.call_no_args("call").inspect_string()).unwrap_or_default(),
+            ];
+            Ok(Value::string(parts.join(" ")))
+        })
+        .singleton_method_with_args("options", ArgSpec::new().keyword_rest(), |_, args| {
+            Ok(args.keyword_rest().unwrap().len().into_value())
+        })
+        .build();
+
+    let mut describe = |code: &str| vm.eval(code).unwrap().as_string().unwrap();
+    assert_eq!("1  0 :a 10 ", describe("ArgSpecTest.new.describe(1, mode: :a)"));
+    assert_eq!("1 2 2 :a 3 4", describe("ArgSpecTest.new.describe(1, 2, 3, 4, mode: :a, scale: 3) { 4 }"));
+    assert_eq!(2, vm.eval("ArgSpecTest.options(a: 1, b: 2)").unwrap().to_i64());
+
+    let mut message = |code: &str| match vm.eval(code) {
+        Err(ErrorKind::Exception(e)) => e.message(),
+        other => panic!("expected an exception, got {:?}", other),
+    };
+    assert_eq!("wrong number of arguments (given 0, expected 1+)", message("ArgSpecTest.new.describe(mode: 1)"));
+    assert_eq!("missing keyword: :mode", message("ArgSpecTest.new.describe(1)"));
+    assert_eq!("unknown keywords: :x, :y", message("ArgSpecTest.new.describe(1, mode: 1, x: 1, y: 2)"));
+    assert_eq!("ArgumentError", vm.eval("begin; ArgSpecTest.new.describe; rescue => e; e.class.name; end")
+        .unwrap().as_string().unwrap());
+}
+
+fn can_control_method_visibility(vm: &mut VM) {
+    use crate::builder::Visibility;
+
+    let base = vm.class("VisibilityBase")
+        .method_fn("inherited", |_, _| Ok(Value::integer(1)))
+        .method_fn("overridden", |_, _| Ok(Value::integer(1)))
+        .build();
+
+    vm.class("VisibilityTest")
+        .extend(base)
+        .private_method_fn("secret", |_, _| Ok(Value::integer(42)))
+        .protected_method_fn("shared", |_, _| Ok(Value::integer(7)))
+        .method_fn("reveal", |receiver, _| Ok(receiver.call_no_args("secret")))
+        .method_fn("compare", |_, args| Ok(args[0].call_no_args("shared")))
+        .method_fn("overridden", |_, _| Ok(Value::integer(2)))
+        .attr_accessor("internal")
+        .visibility("internal=", Visibility::Private)
+        .undef_method("inherited")
+        .remove_method("overridden")
+        .build();
+
+    let mut test = |code: &str| vm.eval(code);
+    assert!(test("VisibilityTest.new.secret").is_err());
+    assert_eq!(42, test("VisibilityTest.new.reveal").unwrap().to_i64());
+    assert!(test("VisibilityTest.new.shared").is_err());
+    assert_eq!(7, test("VisibilityTest.new.compare(VisibilityTest.new)").unwrap().to_i64());
+    assert!(test("VisibilityTest.new.internal").unwrap().is_nil());
+    assert!(test("VisibilityTest.new.internal = 1").is_err());
+    assert!(test("VisibilityTest.private_method_defined?(:internal=)").unwrap().is_true());
+    assert!(test("VisibilityTest.new.respond_to?(:inherited)").unwrap().is_false());
+    assert_eq!(1, test("VisibilityTest.new.overridden").unwrap().to_i64());
+
+    assert!(vm.class("VisibilityTypo").undef_method("no_such_method").try_build().is_err());
+    assert!(vm.class("VisibilityTypo").remove_method("no_such_method").try_build().is_err());
+    assert!(vm.class("VisibilityTypo").visibility("no_such_method", Visibility::Private).try_build().is_err());
+    assert!(vm.class("VisibilityTypo").try_build().is_ok());
+}
+
+#[cfg(feature = "serde")]
+fn can_serialize_with_serde(vm: &mut VM) {
+    use crate::KeyStyle;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        retries: Option<u8>,
+        big: u128,
+        tags: Vec<String>,
+        limits: BTreeMap<String, i64>,
+        shapes: Vec<Shape>,
+    }
+
+    let config = Config {
+        name: "rurust".to_owned(),
+        retries: None,
+        big: 1 << 100,
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        limits: vec![("cpu".to_owned(), 2)].into_iter().collect(),
+        shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { width: 2, height: 3 }],
+    };
+
+    vm.set_global("$config", crate::to_value(&config).unwrap());
+    assert_eq!("rurust", vm.eval("$config[:name]").unwrap().as_string().unwrap());
+    assert!(vm.eval("$config[:retries].nil?").unwrap().is_true());
+    assert!(vm.eval("$config[:big] == 2**100").unwrap().is_true());
+    assert_eq!(2, vm.eval("$config[:limits]['cpu']").unwrap().to_i64());
+    assert!(vm.eval("$config[:shapes] == [:Point, { Circle: 1.5 }, { Rect: { width: 2, height: 3 } }]")
+        .unwrap().is_true());
+    assert_eq!(config, crate::from_value(vm.eval("$config").unwrap()).unwrap());
+
+    let with_strings = crate::to_value_with(&Shape::Rect { width: 1, height: 1 }, KeyStyle::String).unwrap();
+    vm.set_global("$rect", with_strings);
+    assert!(vm.eval("$rect == { 'Rect' => { 'width' => 1, 'height' => 1 } }").unwrap().is_true());
+
+    let from_ruby: Config = crate::from_value(vm.eval(
+        "{ 'name' => 'x', 'retries' => 3, 'big' => 1, 'tags' => [], 'limits' => {}, 'shapes' => ['Point'] }"
+    ).unwrap()).unwrap();
+    assert_eq!(Some(3), from_ruby.retries);
+    assert_eq!(vec![Shape::Point], from_ruby.shapes);
+
+    assert!(crate::from_value::<Config>(vm.eval("{ name: 1 }").unwrap()).is_err());
+    assert!(crate::from_value::<u8>(Value::integer(300)).is_err());
+}
+
+#[cfg(feature = "derive")]
+fn can_derive_ruby_classes(vm: &mut VM) {
+    use crate::{ruby_methods, RubyClass};
+
+    #[derive(RubyClass)]
+    #[ruby(name = "DerivedPoint")]
+    struct Point {
+        #[ruby(reader)]
+        x: i64,
+        #[ruby(accessor)]
+        y: i64,
+        label: String,
+    }
+
+    #[ruby_methods]
+    impl Point {
+        pub fn new(x: i64, y: i64) -> Result<Self, ErrorKind> {
+            if x < 0 {
+                return Err(ErrorKind::VM("x must not be negative".to_owned()));
+            }
+            Ok(Point { x, y, label: String::new() })
+        }
+
+        pub fn origin() -> Self { Point { x: 0, y: 0, label: "origin".to_owned() } }
+
+        pub fn sum(&self) -> i64 { self.x + self.y }
+
+        pub fn flipped(&self) -> Self { Point { x: self.y, y: self.x, label: self.label.clone() } }
+
+        #[ruby(name = "label=")]
+        pub fn set_label(&mut self, label: String) { self.label = label; }
+
+        pub fn label(&self) -> String { self.label.clone() }
+
+        #[ruby(name = "scaled_sum")]
+        pub fn scaled(&self, factor: i64) -> Result<i64, ConversionError> {
+            self.sum().checked_mul(factor).ok_or(ConversionError::OutOfRange { target: "i64" })
+        }
+
+        #[ruby(skip)]
+        #[allow(dead_code)]
+        pub fn hidden(&self) { }
+
+        #[allow(dead_code)]
+        fn private_helper(&self) { }
+    }
+
+    vm.define_class::<Point>();
+
+    #[derive(RubyClass)]
+    #[ruby(name = "DerivedHolder", typed_data = false)]
+    struct Holder {
+        #[ruby(reader)]
+        held: Value,
+    }
+
+    impl TypedData for Holder {
+        fn mark(&self) { typed_data::mark(self.held); }
+    }
+
+    #[ruby_methods]
+    impl Holder {
+        pub fn new(held: Value) -> Self { Holder { held } }
+    }
+
+    vm.define_class::<Holder>();
+
+    assert_eq!(5, vm.eval("p = DerivedPoint.new(2, 3); p.sum").unwrap().to_i64());
+    assert_eq!(2, vm.eval("p.x").unwrap().to_i64());
+    assert_eq!(10, vm.eval("p.y = 8; p.sum").unwrap().to_i64());
+    assert_eq!("hi", vm.eval("p.label = 'hi'; p.label").unwrap().as_string().unwrap());
+    assert_eq!(30, vm.eval("p.scaled_sum(3)").unwrap().to_i64());
+    assert!(vm.eval("p.scaled_sum(2**62)").is_err());
+    assert!(vm.eval("p.respond_to?(:x=)").unwrap().is_false());
+    assert!(vm.eval("p.respond_to?(:hidden)").unwrap().is_false());
+    assert!(vm.eval("p.respond_to?(:private_helper)").unwrap().is_false());
+    assert!(vm.eval("p.sum(1)").is_err());
+    assert!(vm.eval("DerivedPoint.new(-1, 0)").is_err());
+    assert!(vm.eval("DerivedPoint.new('a', 0)").is_err());
+    assert!(vm.eval("DerivedPoint.allocate.sum").is_err());
+    assert_eq!(0, vm.eval("DerivedPoint.origin").unwrap().call_no_args("sum").to_i64());
+    // Methods returning `Self` wrap the result in the receiver's class, not its singleton class.
+    assert!(vm.eval("q = DerivedPoint.new(1, 2); def q.extra; end; q.extend(Comparable); r = q.flipped; \
+                     r.class == DerivedPoint && r.singleton_methods.empty? && !r.is_a?(Comparable) && r.x == 2")
+        .unwrap().is_true());
+    assert_eq!("kept", vm.eval("h = DerivedHolder.new('ke' + 'pt'); GC.start; h.held").unwrap()
+        .as_string().unwrap());
+}
@@ -0,0 +1,179 @@
+//! Wrapping Rust values in Ruby objects.
+//!
+//! A type implementing `TypedData` can be stored inside a Ruby object.
+//! The Ruby object owns the Rust value, which is dropped when the object
+//! is garbage collected.
+
+use crate::{ffi, libc, ConversionError, Value};
+
+use std::any::{self, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::{mem, ptr};
+
+/// A Rust type that can be wrapped in a Ruby object.
+pub trait TypedData: Sized + 'static {
+    /// Marks any Ruby values held by this value, so they are not garbage collected.
+    ///
+    /// Call `typed_data::mark` on each of them.
+    fn mark(&self) { }
+
+    /// Called when the owning Ruby object is garbage collected.
+    ///
+    /// This must not call into Ruby.
+    fn free(self) { }
+
+    /// The approximate memory usage of the value, in bytes.
+    fn memsize(&self) -> usize { mem::size_of::<Self>() }
+}
+
+/// The data pointer of a wrapped object.
+///
+/// Objects created by an allocation function start out empty and are
+/// filled in later, usually by `initialize`.
+type Slot<T> = RefCell<Option<T>>;
+
+lazy_static! {
+    /// Leaked data types, one per Rust type.
+    static ref DATA_TYPES: Mutex<HashMap<TypeId, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Marks a Ruby value as in use.
+///
+/// Only valid inside `TypedData::mark`.
+pub fn mark(value: Value) {
+    unsafe { ffi::rb_gc_mark(value.0) }
+}
+
+/// Creates a Ruby object of the given class that owns a Rust value.
+pub fn wrap<T>(class: Value, data: T) -> Value where T: TypedData {
+    new_object(class, Some(data))
+}
+
+/// Allocates an empty object for a class wrapping `T`.
+///
+/// Used as an allocation function by `builder::Class::typed_data`.
+pub extern "C" fn allocate<T>(class: ffi::VALUE) -> ffi::VALUE where T: TypedData {
+    new_object::<T>(Value::from(class), None).0
+}
+
+/// Checks if a value wraps a `T`.
+pub fn is_wrapping<T>(value: Value) -> bool where T: TypedData {
+    unsafe { ffi::rb_typeddata_is_kind_of(value.0, data_type::<T>()) != 0 }
+}
+
+/// Stores a Rust value inside an object.
+///
+/// Any previous value is released with `TypedData::free`, as it would
+/// be if the object were garbage collected.
+pub fn set<T>(value: Value, data: T) -> Result<(), ConversionError> where T: TypedData {
+    match slot::<T>(&value) {
+        Some(slot) => match slot.try_borrow_mut() {
+            Ok(mut slot) => {
+                let previous = slot.replace(data);
+                drop(slot);
+
+                if let Some(previous) = previous {
+                    previous.free();
+                }
+                Ok(())
+            },
+            Err(..) => Err(ConversionError::AlreadyBorrowed),
+        },
+        None => Err(ConversionError::wrong_type(any::type_name::<T>(), value)),
+    }
+}
+
+/// Borrows the Rust value inside an object.
+///
+/// Returns `None` if the object does not wrap a `T`, is empty,
+/// or is already mutably borrowed.
+pub fn borrow<T>(value: &Value) -> Option<Ref<'_, T>> where T: TypedData {
+    let slot = slot::<T>(value)?.try_borrow().ok()?;
+    Ref::filter_map(slot, Option::as_ref).ok()
+}
+
+/// Mutably borrows the Rust value inside an object.
+///
+/// Returns `None` if the object does not wrap a `T`, is empty,
+/// or is already borrowed.
+pub fn borrow_mut<T>(value: &Value) -> Option<RefMut<'_, T>> where T: TypedData {
+    let slot = slot::<T>(value)?.try_borrow_mut().ok()?;
+    RefMut::filter_map(slot, Option::as_mut).ok()
+}
+
+/// Checks if a value wraps a `T` that is currently borrowed.
+pub(crate) fn is_borrowed<T>(value: Value) -> bool where T: TypedData {
+    slot::<T>(&value).map(|slot| slot.try_borrow_mut().is_err()).unwrap_or(false)
+}
+
+fn new_object<T>(class: Value, data: Option<T>) -> Value where T: TypedData {
+    let slot: Box<Slot<T>> = Box::new(RefCell::new(data));
+
+    Value::from(unsafe {
+        ffi::rb_data_typed_object_wrap(class.0, Box::into_raw(slot) as *mut libc::c_void,
+                                       data_type::<T>())
+    })
+}
+
+fn slot<T>(value: &Value) -> Option<&Slot<T>> where T: TypedData {
+    if is_wrapping::<T>(*value) {
+        unsafe { Some(&*(ffi::rb_check_typeddata(value.0, data_type::<T>()) as *const Slot<T>)) }
+    } else {
+        None
+    }
+}
+
+/// Gets the Ruby data type describing `T`.
+fn data_type<T>() -> *const ffi::rb_data_type_t where T: TypedData {
+    let mut data_types = DATA_TYPES.lock().unwrap();
+
+    let data_type = data_types.entry(TypeId::of::<T>()).or_insert_with(|| {
+        let name = CString::new(any::type_name::<T>()).unwrap();
+
+        let data_type = Box::new(ffi::rb_data_type_t {
+            wrap_struct_name: name.into_raw(),
+            function: ffi::rb_data_type_function {
+                dmark: Some(mark_slot::<T>),
+                dfree: Some(free_slot::<T>),
+                dsize: Some(slot_size::<T>),
+                dcompact: None,
+                reserved: [ptr::null_mut()],
+            },
+            parent: ptr::null(),
+            data: ptr::null_mut(),
+            flags: ffi::VALUE(ffi::RUBY_TYPED_FREE_IMMEDIATELY),
+        });
+
+        Box::into_raw(data_type) as usize
+    });
+
+    *data_type as *const ffi::rb_data_type_t
+}
+
+extern "C" fn mark_slot<T>(data: *mut libc::c_void) where T: TypedData {
+    // The GC can run while the value is mutably borrowed, so
+    // read through the cell rather than borrowing it.
+    let slot = unsafe { &*(*(data as *const Slot<T>)).as_ptr() };
+
+    if let Some(ref value) = *slot {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| value.mark()));
+    }
+}
+
+extern "C" fn free_slot<T>(data: *mut libc::c_void) where T: TypedData {
+    let slot = unsafe { Box::from_raw(data as *mut Slot<T>) };
+
+    if let Some(value) = slot.into_inner() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| value.free()));
+    }
+}
+
+extern "C" fn slot_size<T>(data: *const libc::c_void) -> libc::size_t where T: TypedData {
+    let slot = unsafe { &*(*(data as *const Slot<T>)).as_ptr() };
+
+    mem::size_of::<Slot<T>>() + slot.as_ref().map(|value| value.memsize()).unwrap_or(0)
+}
@@ -1,10 +1,18 @@
-use crate::{builder, ffi, libc, util};
+use crate::{block, builder, ffi, libc, typed_data, util, ConversionError, ErrorKind, RString, RootedValue, TryFromValue, TypedData, VM};
+use std::cell::{Ref, RefMut};
+use std::marker::PhantomData;
 use std::{cmp, fmt};
 
 /// A Ruby value.
+///
+/// Values are not kept alive by Rust. See the `gc` module for
+/// when a value needs rooting.
+///
+/// Ruby can only be used from the thread that created the VM,
+/// so values cannot be sent to other threads.
 #[derive(Copy,Clone)]
 #[repr(transparent)]
-pub struct Value(pub ffi::VALUE);
+pub struct Value(pub ffi::VALUE, PhantomData<*const ()>);
 
 impl Value
 {
@@ -33,15 +41,36 @@ impl Value
         Self::from(unsafe { ffi::rb_id2sym(Self::intern(name.as_ref())) })
     }
 
-    /// Creates a new String.
+    /// Creates a new UTF-8 `String`.
     pub fn string<S>(s: S) -> Self where S: AsRef<str> {
-        Self::from(unsafe { ffi::rb_id2str(Self::intern(s.as_ref())) })
+        RString::new(s.as_ref()).value()
+    }
+
+    /// Creates a new binary `String`.
+    pub fn bytes(bytes: &[u8]) -> Self {
+        RString::from_bytes(bytes).value()
+    }
+
+    /// Creates a new `String` in the named encoding.
+    pub fn string_with_encoding(bytes: &[u8], encoding: &str) -> Result<Self, ErrorKind> {
+        RString::with_encoding(bytes, encoding).map(|s| s.value())
+    }
+
+    /// Creates a frozen, deduplicated `String`, like a frozen string literal.
+    pub fn frozen_string<S>(s: S) -> Self where S: AsRef<str> {
+        RString::frozen(s.as_ref()).value()
     }
 
     /// Creates a new `Integer`.
+    ///
+    /// Values too large for a Fixnum become a Bignum.
     pub fn integer<I>(v: I) -> Self where I: Into<i64> {
-        // FIXME: this can overflow and panic
-        Self::from(ffi::INT2FIX(v.into() as usize))
+        Self::from(unsafe { ffi::rb_ll2inum(v.into() as libc::c_longlong) })
+    }
+
+    /// Creates a new `Integer` from an unsigned number.
+    pub fn unsigned_integer<I>(v: I) -> Self where I: Into<u64> {
+        Self::from(unsafe { ffi::rb_ull2inum(v.into() as libc::c_ulonglong) })
     }
 
     /// Creates a new `Float`.
@@ -49,6 +78,18 @@ impl Value
         Self::from(unsafe { ffi::rb_float_new(v.into()) })
     }
 
+    /// Roots the value so it is not garbage collected.
+    pub fn root(self) -> RootedValue {
+        RootedValue::new(self)
+    }
+
+    /// Converts the value into a Rust value.
+    ///
+    /// Fails without raising if the value is of the wrong type.
+    pub fn try_convert<T>(self) -> Result<T, ConversionError> where T: TryFromValue {
+        T::try_from_value(self)
+    }
+
     /// Converts the value into a symbol.
     pub fn to_sym(&self) -> Value {
         Self::from(unsafe { ffi::rb_to_symbol(self.0) })
@@ -69,6 +110,32 @@ impl Value
         unsafe { ffi::rb_num2dbl(self.0) as f64 }
     }
 
+    /// Converts an `Integer` to a 64-bit signed integer.
+    ///
+    /// Fails without raising if the value is not an `Integer` or is out of range.
+    pub fn try_to_i64(&self) -> Result<i64, ErrorKind> {
+        Ok(self.try_convert()?)
+    }
+
+    /// Converts an `Integer` to a 64-bit unsigned integer.
+    ///
+    /// Fails without raising if the value is not an `Integer` or is out of range.
+    pub fn try_to_u64(&self) -> Result<u64, ErrorKind> {
+        Ok(self.try_convert()?)
+    }
+
+    /// Converts an `Integer` to a 128-bit signed integer.
+    ///
+    /// Fails without raising if the value is not an `Integer` or is out of range.
+    pub fn try_to_i128(&self) -> Result<i128, ErrorKind> {
+        Ok(self.try_convert()?)
+    }
+
+    /// Converts the value into a 64-bit float, catching any exceptions.
+    pub fn try_to_f64(&self) -> Result<f64, ErrorKind> {
+        VM::protect(|| self.to_f64())
+    }
+
     /// Gets the class.
     pub fn class(&self) -> Value {
         Self::from(unsafe { ffi::rb_class_of(self.0) })
@@ -80,6 +147,12 @@ impl Value
         builder::Class::new_under(name, Some(self))
     }
 
+    /// Creates a nested exception class.
+    pub fn nested_exception_class<S>(self, name: S) -> builder::ExceptionClass
+        where S: Into<String> {
+        builder::ExceptionClass::new_under(name, Some(self))
+    }
+
     /// Creates a nested module.
     pub fn nested_module<S>(self, name: S) -> builder::Module
         where S: Into<String> {
@@ -113,7 +186,9 @@ impl Value
     /// Checks if the value is a regex.
     pub fn is_regex(&self) -> bool { ffi::TYPE_P(self.0, ffi::T_REGEXP) }
     /// Checks if the value is an `Integer` type.
-    pub fn is_integer(&self) -> bool { ffi::TYPE_P(self.0, ffi::T_FIXNUM) }
+    pub fn is_integer(&self) -> bool {
+        ffi::TYPE_P(self.0, ffi::T_FIXNUM) || ffi::TYPE_P(self.0, ffi::T_BIGNUM)
+    }
     /// Checks if the value is a complex number.
     pub fn is_complex_number(&self) -> bool { ffi::TYPE_P(self.0, ffi::T_COMPLEX) }
     /// Checks if the value is a rational number.
@@ -142,13 +217,10 @@ impl Value
 
     /// Converts a Ruby `String` into a Rust `String`.
     /// Returns `None` if the value is not a Ruby `String`.
+    ///
+    /// Bytes that are not valid UTF-8 are replaced with `U+FFFD`.
     pub fn as_string(&self) -> Option<String> {
-        if self.is_string() {
-            let c_str = unsafe { ffi::rb_string_value_cstr(&self.0 as *const _) };
-            Some(util::string(c_str))
-        } else {
-            None
-        }
+        RString::from_value(*self).map(|s| s.to_str_lossy())
     }
 
     /// Calls a method with no args.
@@ -156,6 +228,11 @@ impl Value
         Self::from(unsafe { ffi::rb_funcall(self.0, Self::intern(method_name), 0) })
     }
 
+    /// Calls a method with no args, catching any exceptions.
+    pub fn try_call_no_args(&self, method_name: &str) -> Result<Self, ErrorKind> {
+        self.try_send(method_name, &[])
+    }
+
     /// Sends a message to the value.
     pub fn send(&self, method_name: &str, args: &[Self]) -> Self {
         Self::from(unsafe {
@@ -168,6 +245,59 @@ impl Value
         })
     }
 
+    /// Sends a message to the value, catching any exceptions.
+    pub fn try_send(&self, method_name: &str, args: &[Self]) -> Result<Self, ErrorKind> {
+        let id = Self::intern(method_name);
+
+        VM::protect(|| Self::from(unsafe {
+            ffi::rb_funcallv(self.0, id, args.len() as libc::c_int, args.as_ptr() as *const _)
+        }))
+    }
+
+    /// Sends a message to the value with a Rust closure as the block.
+    ///
+    /// The closure is called with the values yielded to the block.
+    pub fn send_with_block<F>(&self, method_name: &str, args: &[Self], f: F) -> Result<Self, ErrorKind>
+        where F: FnMut(&[Self]) -> Result<Self, ErrorKind> {
+        block::call_with_block(*self, method_name, args, f)
+    }
+
+    /// Creates a `Proc` that calls a closure with its arguments.
+    pub fn proc_from<F>(f: F) -> Self
+        where F: Fn(&[Self]) -> Result<Self, ErrorKind> + 'static {
+        block::new_proc(Box::new(f), None)
+    }
+
+    /// Creates a lambda that calls a closure with its arguments.
+    ///
+    /// Ruby raises `ArgumentError` unless the lambda is called with exactly
+    /// `arity` arguments. A negative arity accepts any number of arguments.
+    pub fn lambda_from<F>(arity: i32, f: F) -> Self
+        where F: Fn(&[Self]) -> Result<Self, ErrorKind> + 'static {
+        block::new_proc(Box::new(f), Some(arity))
+    }
+
+    /// Calls a `Proc` or other callable object, catching any exceptions.
+    pub fn call_proc(&self, args: &[Self]) -> Result<Self, ErrorKind> {
+        self.try_send("call", args)
+    }
+
+    /// Borrows the Rust value wrapped by this object.
+    ///
+    /// Returns `None` if the object does not wrap a `T`, has not been
+    /// initialised, or is already mutably borrowed.
+    pub fn downcast_ref<T>(&self) -> Option<Ref<'_, T>> where T: TypedData {
+        typed_data::borrow(self)
+    }
+
+    /// Mutably borrows the Rust value wrapped by this object.
+    ///
+    /// Returns `None` if the object does not wrap a `T`, has not been
+    /// initialised, or is already borrowed.
+    pub fn downcast_mut<T>(&self) -> Option<RefMut<'_, T>> where T: TypedData {
+        typed_data::borrow_mut(self)
+    }
+
     /// Gets the value of an instance variable by name.
     /// Returns `nil` if it doesn't exist.
     pub fn get_ivar(&self, name: &str) -> Self {
@@ -179,7 +309,7 @@ impl Value
         Self::from(unsafe { ffi::rb_iv_set(self.0, util::c_string(name).as_ptr(), value.0) })
     }
 
-    fn intern(s: &str) -> ffi::ID {
+    pub(crate) fn intern(s: &str) -> ffi::ID {
         unsafe { ffi::rb_intern(util::c_string(s).as_ptr()) }
     }
 }
@@ -210,7 +340,7 @@ impl fmt::Debug for Value
 impl From<ffi::VALUE> for Value
 {
     fn from(value: ffi::VALUE) -> Value {
-        Value(value)
+        Value(value, PhantomData)
     }
 }
 
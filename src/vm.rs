@@ -1,24 +1,63 @@
-use crate::{builder, ffi, util, Value};
+use crate::{builder, callback, classes, ffi, global, util, ConversionError, ExceptionDetails, RubyClass, RubyError, RubyException, RubyMethods, Value};
 
 use std;
 use std::fmt;
+use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
 use libc;
 
 use std::sync::Mutex;
+use std::thread::{self, ThreadId};
 
 /// A Ruby virtual machine.
 pub struct VM;
 
+/// Passed to printf-style MRI functions so messages are never
+/// interpreted as format strings.
+const FORMAT_STRING: &[u8] = b"%s\0";
+
 /// We only want to be able to have one `VM` at a time.
 static mut VM_EXISTS: bool = false;
 
 lazy_static! {
     static ref ACTIVE_VM: Mutex<VM> = Mutex::new(VM::new().expect("failed to create Ruby VM"));
+    /// The thread that created the VM, the only thread allowed to use it.
+    static ref VM_THREAD: Mutex<Option<ThreadId>> = Mutex::new(None);
+}
+
+/// A builder for configuring a new Ruby VM.
+///
+/// The options are applied as if they were given on the `ruby` command
+/// line, before any code is evaluated.
+#[derive(Clone, Debug)]
+pub struct VMBuilder
+{
+    program_name: String,
+    load_paths: Vec<String>,
+    warning_level: Option<u8>,
+    external_encoding: Option<String>,
+    internal_encoding: Option<String>,
+    gems: bool,
+    flags: Vec<String>,
 }
 
-// TODO:
-// Implement hooked variables (rb_define_hooked_variable)
-//   Allows a callback to get/set variable value
+/// Options for evaluating code with `VM::eval_with`.
+#[derive(Clone, Debug, Default)]
+pub struct EvalOptions
+{
+    /// The file name reported in errors and backtraces.
+    /// Defaults to `(eval)`.
+    pub filename: Option<String>,
+    /// The line number of the first line of code.
+    /// Defaults to `1`.
+    pub line: Option<i32>,
+    /// The `Binding` to evaluate in.
+    /// Defaults to `TOPLEVEL_BINDING`.
+    pub binding: Option<Value>,
+    /// Evaluates with this object as `self` via `instance_eval`.
+    /// Takes precedence over `binding`.
+    pub self_object: Option<Value>,
+}
 
 #[derive(PartialEq)]
 /// A Ruby error
@@ -27,28 +66,60 @@ pub enum ErrorKind
     /// An internal VM error.
     VM(String),
     /// An exception was thrown.
-    Exception(Value),
+    Exception(RubyException),
+    /// A Ruby value could not be converted into a Rust value.
+    Conversion(ConversionError),
+    /// Ruby is unwinding the stack for something other than an exception,
+    /// such as `break` out of a block or `throw`.
+    ///
+    /// Holds the VM's jump tag. Return it to Ruby promptly, without
+    /// calling back into Ruby first, so the unwinding can carry on.
+    Jump(libc::c_int),
+}
+
+/// An `ErrorKind` with any exception captured as Rust values.
+///
+/// Unlike `ErrorKind`, this can be sent to other threads, so it works with
+/// `anyhow` and `Box<dyn Error + Send + Sync>`. Converted with `From`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OwnedError
+{
+    /// An internal VM error.
+    VM(String),
+    /// An exception was thrown.
+    Exception(ExceptionDetails),
+    /// A Ruby value could not be converted into a Rust value.
+    Conversion(ConversionError),
+    /// Ruby was unwinding the stack for something other than an exception.
+    Jump(libc::c_int),
 }
 
 impl VM
 {
     /// Gets the active VM.
+    ///
+    /// The VM is created on first use, and can only be used from the
+    /// thread that created it. Use a `VmExecutor` to call Ruby from
+    /// multiple threads.
+    ///
+    /// Panics if called from any other thread.
     pub fn get() -> &'static Mutex<VM> {
-        &ACTIVE_VM
+        let vm = &*ACTIVE_VM;
+
+        if *VM_THREAD.lock().unwrap() != Some(thread::current().id()) {
+            panic!("the Ruby VM can only be used from the thread that created it");
+        }
+        vm
     }
 
-    /// Creates a new Ruby VM.
+    /// Creates a new Ruby VM with the default options.
     pub fn new() -> Result<Self, ErrorKind> {
-        unsafe {
-            if VM_EXISTS {
-                Err(ErrorKind::VM("can only have one Ruby VM at a time".to_owned()))
-            } else {
-                ffi::ruby_init();
-                VM_EXISTS = true;
+        VMBuilder::new().build()
+    }
 
-                Ok(VM)
-            }
-        }
+    /// Creates a builder for configuring a new Ruby VM.
+    pub fn builder() -> VMBuilder {
+        VMBuilder::new()
     }
 
     /// Evaluates a line of code.
@@ -63,9 +134,204 @@ impl VM
         self.eval_advanced(code, true)
     }
 
+    /// Evaluates code with a file name, line number and scope.
+    pub fn eval_with(&mut self, code: &str, options: EvalOptions) -> Result<Value, ErrorKind> {
+        let args = [
+            Value::string(code),
+            Value::string(options.filename.as_deref().unwrap_or("(eval)")),
+            Value::integer(options.line.unwrap_or(1)),
+        ];
+
+        if let Some(receiver) = options.self_object {
+            receiver.try_send("instance_eval", &args)
+        } else {
+            let binding = match options.binding {
+                Some(binding) => binding,
+                None => classes::Object().try_send("const_get", &[Value::symbol("TOPLEVEL_BINDING")])?,
+            };
+
+            binding.try_send("eval", &args)
+        }
+    }
+
     /// `require`s a file.
-    pub fn require(&self, file_name: &str) -> Value {
-        Value::from(unsafe { ffi::rb_require(util::c_string(file_name).as_ptr()) })
+    ///
+    /// Returns `false` if the file was already loaded. Failing to
+    /// find or load the file gives a `LoadError` exception.
+    pub fn require(&self, file_name: &str) -> Result<bool, ErrorKind> {
+        let file_name = util::c_string(file_name);
+        VM::protect(|| Value::from(unsafe { ffi::rb_require(file_name.as_ptr()) }))
+            .map(|loaded| loaded.is_true())
+    }
+
+    /// `load`s a file, running it even if it has been loaded before.
+    ///
+    /// If `wrap` is set, the file runs under an anonymous module so
+    /// it cannot pollute the global namespace.
+    pub fn load_file<P>(&mut self, path: P, wrap: bool) -> Result<(), ErrorKind>
+        where P: AsRef<Path> {
+        let path = Value::string(path.as_ref().to_string_lossy());
+        VM::protect(|| unsafe { ffi::rb_load(path.0, wrap as libc::c_int) })
+    }
+
+    /// Runs a file as the main script.
+    ///
+    /// Sets `$0` to the path and `ARGV` to the arguments. A script
+    /// calling `exit` with a successful status is not an error.
+    pub fn run_script<P, S>(&mut self, path: P, argv: &[S]) -> Result<(), ErrorKind>
+        where P: AsRef<Path>, S: AsRef<str> {
+        let path = path.as_ref();
+        self.set_script_name(&path.to_string_lossy());
+
+        let args: Vec<_> = argv.iter().map(|arg| util::c_string(arg.as_ref())).collect();
+        let mut arg_ptrs: Vec<_> = args.iter().map(|arg| arg.as_ptr() as *mut libc::c_char).collect();
+        unsafe { ffi::ruby_set_argv(arg_ptrs.len() as libc::c_int, arg_ptrs.as_mut_ptr()) };
+
+        match self.load_file(path, false) {
+            Err(ErrorKind::Exception(e)) if e.is_kind_of(classes::SystemExit()) => {
+                if e.value().try_call_no_args("success?")?.is_true() {
+                    Ok(())
+                } else {
+                    Err(ErrorKind::Exception(e))
+                }
+            },
+            result => result,
+        }
+    }
+
+    /// Runs a function without holding the Global VM Lock.
+    ///
+    /// Other Ruby threads can run while the function does.
+    ///
+    /// The function cannot be interrupted, so `Thread#kill` and signals
+    /// wait for it to finish. See `without_gvl_interruptible`.
+    ///
+    /// Ruby checks for pending interrupts before and after running the
+    /// function, so `Thread#raise` or `Thread#kill` may still cause an
+    /// error, in which case the function may not have run at all.
+    ///
+    /// # Safety
+    ///
+    /// The function must not call into Ruby or touch any Ruby values,
+    /// including values it captures and the `VM` itself. Doing so
+    /// without the lock is undefined behaviour.
+    pub unsafe fn without_gvl<F, T>(f: F) -> Result<T, ErrorKind>
+        where F: FnOnce() -> T {
+        VM::call_without_gvl(f, None::<fn()>)
+    }
+
+    /// Runs a function without holding the Global VM Lock, with a way
+    /// to interrupt it.
+    ///
+    /// When Ruby wants to interrupt the thread, for example because of
+    /// `Thread#kill` or a signal, it calls `unblock` from another thread.
+    /// `unblock` should make the function return as soon as possible.
+    /// Any result is then discarded and the interrupt returned as an error.
+    ///
+    /// # Safety
+    ///
+    /// Neither `f` nor `unblock` may call into Ruby or touch any Ruby
+    /// values. See `without_gvl`.
+    pub unsafe fn without_gvl_interruptible<F, U, T>(f: F, unblock: U) -> Result<T, ErrorKind>
+        where F: FnOnce() -> T, U: Fn() + Sync {
+        VM::call_without_gvl(f, Some(unblock))
+    }
+
+    unsafe fn call_without_gvl<F, U, T>(f: F, unblock: Option<U>) -> Result<T, ErrorKind>
+        where F: FnOnce() -> T, U: Fn() + Sync {
+        extern "C" fn call<F, T>(data: *mut libc::c_void) -> *mut libc::c_void
+            where F: FnOnce() -> T {
+            let data = unsafe { &mut *(data as *mut ProtectData<F, T>) };
+            let f = data.f.take().unwrap();
+
+            data.result = Some(panic::catch_unwind(AssertUnwindSafe(f)));
+            std::ptr::null_mut()
+        }
+
+        extern "C" fn call_unblock<U>(unblock: *mut libc::c_void) where U: Fn() + Sync {
+            let unblock = unsafe { &*(unblock as *const U) };
+            let _ = panic::catch_unwind(AssertUnwindSafe(unblock));
+        }
+
+        let mut data = ProtectData { f: Some(f), result: None };
+
+        let (ubf, ubf_data) = match unblock {
+            Some(ref unblock) => (Some(call_unblock::<U> as extern "C" fn(*mut libc::c_void)),
+                                  unblock as *const U as *mut libc::c_void),
+            None => (None, std::ptr::null_mut()),
+        };
+
+        // Pending interrupts are raised around the call, and must not
+        // unwind over the function or its result.
+        let data_ptr = &mut data as *mut _ as *mut libc::c_void;
+        VM::protect(|| {
+            ffi::rb_thread_call_without_gvl(call::<F, T>, data_ptr, ubf, ubf_data);
+        })?;
+
+        match data.result.unwrap() {
+            Ok(result) => Ok(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Runs a function, catching any Ruby exceptions it raises.
+    ///
+    /// Built on `rb_protect`. If an exception is raised, Ruby unwinds
+    /// straight out of the function without running destructors for any
+    /// values inside it, so keep the function to the raw Ruby calls.
+    pub fn protect<F, T>(f: F) -> Result<T, ErrorKind>
+        where F: FnOnce() -> T {
+        VM::protect_raw(f).map_err(|state| {
+            if state != ffi::TAG_RAISE && state != ffi::TAG_FATAL {
+                // The VM still needs the jump's error info to resume it.
+                return ErrorKind::Jump(state);
+            }
+
+            let exception = Value::from(unsafe { ffi::rb_errinfo() });
+            unsafe { ffi::rb_set_errinfo(ffi::Qnil) };
+
+            ErrorKind::Exception(exception.into())
+        })
+    }
+
+    /// Runs a function like `protect`, but returns the jump tag on failure.
+    ///
+    /// Any exception is left in the VM's error info.
+    pub(crate) fn protect_raw<F, T>(f: F) -> Result<T, libc::c_int>
+        where F: FnOnce() -> T {
+        extern "C" fn call<F, T>(data: ffi::VALUE) -> ffi::VALUE
+            where F: FnOnce() -> T {
+            let data = unsafe { &mut *(data.0 as *mut ProtectData<F, T>) };
+            let f = data.f.take().unwrap();
+
+            // Panics cannot unwind through Ruby, resume them on the other side.
+            data.result = Some(panic::catch_unwind(AssertUnwindSafe(f)));
+            ffi::Qnil
+        }
+
+        let mut data = ProtectData { f: Some(f), result: None };
+        let mut state: libc::c_int = 0;
+
+        unsafe {
+            ffi::rb_protect(call::<F, T>, ffi::VALUE(&mut data as *mut _ as ffi::INNER_VALUE), &mut state);
+        }
+
+        if state == 0 {
+            match data.result.unwrap() {
+                Ok(result) => Ok(result),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        } else {
+            Err(state)
+        }
+    }
+
+    /// Defines the class for a Rust type, along with its attributes and methods.
+    ///
+    /// Usually used with types deriving `RubyClass` with a `ruby_methods` impl.
+    pub fn define_class<T>(&mut self) -> Value where T: RubyClass + RubyMethods {
+        let class = self.class(T::NAME).typed_data::<T>();
+        T::define_methods(T::define_attributes(class)).build()
     }
 
     /// Creates a new class.
@@ -74,6 +340,12 @@ impl VM
         builder::Class::new(name)
     }
 
+    /// Creates a new exception class.
+    pub fn exception_class<S>(&mut self, name: S) -> builder::ExceptionClass
+        where S: Into<String> {
+        builder::ExceptionClass::new(name)
+    }
+
     /// Creates a new module.
     pub fn module<S>(&mut self, name: S) -> builder::Module
         where S: Into<String> {
@@ -90,6 +362,35 @@ impl VM
         Value::from(unsafe { ffi::rb_gv_get(util::c_string(name).as_ptr()) })
     }
 
+    /// Defines a global variable whose reads and writes go through Rust.
+    ///
+    /// The variable stores a value, starting with `initial`. Reads return
+    /// whatever the getter returns when given the stored value. Writes store
+    /// whatever the setter returns when given the assigned value.
+    pub fn define_hooked_global<G, S>(&self, name: &str, initial: Value, getter: G, setter: S)
+        where G: Fn(Value) -> Result<Value, ErrorKind> + 'static,
+              S: Fn(Value) -> Result<Value, ErrorKind> + 'static {
+        global::define_hooked(name, initial, Box::new(getter), Box::new(setter));
+    }
+
+    /// Defines a global variable with no storage of its own.
+    ///
+    /// Reads call the getter and writes call the setter.
+    pub fn define_virtual_global<G, S>(&self, name: &str, getter: G, setter: S)
+        where G: Fn() -> Result<Value, ErrorKind> + 'static,
+              S: Fn(Value) -> Result<(), ErrorKind> + 'static {
+        global::define_virtual(name, Box::new(move |_| getter()),
+                               Some(Box::new(move |value| setter(value).map(|_| Value::nil()))));
+    }
+
+    /// Defines a global variable that cannot be assigned to.
+    ///
+    /// Reads call the getter. Writes raise a `NameError`.
+    pub fn define_readonly_global<G>(&self, name: &str, getter: G)
+        where G: Fn() -> Result<Value, ErrorKind> + 'static {
+        global::define_virtual(name, Box::new(move |_| getter()), None);
+    }
+
     /// Sets a global constant.
     pub fn set_global_const(&self, name: &str, value: Value) {
         unsafe { ffi::rb_define_global_const(util::c_string(name).as_ptr(), value.0) }
@@ -106,29 +407,49 @@ impl VM
         }
     }
 
+    /// Defines a global function implemented by a Rust closure.
+    ///
+    /// Panics inside the closure are raised as Ruby exceptions.
+    pub fn define_global_function_fn<F>(&self, name: &str, f: F)
+        where F: Fn(Value, &[Value]) -> Result<Value, ErrorKind> + 'static {
+        let kernel = Value::from(unsafe { ffi::rb_mKernel });
+        callback::define_module_function(kernel, name, Box::new(f));
+    }
+
+    /// Sets the class of exception raised when a Rust callback panics.
+    ///
+    /// Defaults to `RuntimeError`, which is also raised if creating an
+    /// instance of the class fails.
+    pub fn set_panic_class(&self, class: Value) {
+        callback::set_panic_class(class);
+    }
+
     /// Gets the current receiver (can be `nil`).
     pub fn current_receiver(&self) -> Value {
         unsafe { Value::from(ffi::rb_current_receiver()) }
     }
 
     /// Raises an object and a message.
+    ///
+    /// This longjmps straight over any Rust frames, skipping their destructors.
+    /// Inside closure-based methods, return an `Err` instead.
     pub fn raise(&self, value: Value, message: &str) -> ! {
-        unsafe { ffi::rb_raise(value.0, util::c_string(message).as_ptr()) }
+        RubyException::new(value, message).raise()
     }
 
     /// Raises a fatal error.
     pub fn fatal(&self, message: &str) -> ! {
-        unsafe { ffi::rb_fatal(util::c_string(message).as_ptr()) }
+        unsafe { ffi::rb_fatal(FORMAT_STRING.as_ptr() as *const _, util::c_string(message).as_ptr()) }
     }
 
     /// Raises a bug.
     pub fn bug(&self, message: &str) -> ! {
-        unsafe { ffi::rb_bug(util::c_string(message).as_ptr()) }
+        unsafe { ffi::rb_bug(FORMAT_STRING.as_ptr() as *const _, util::c_string(message).as_ptr()) }
     }
 
     /// Logs a Ruby warning.
     pub fn warning(&self, message: &str) {
-        unsafe { ffi::rb_warning(util::c_string(message).as_ptr()) }
+        unsafe { ffi::rb_warning(FORMAT_STRING.as_ptr() as *const _, util::c_string(message).as_ptr()) }
     }
 
     /// Prints Ruby version info to stdout.
@@ -170,11 +491,17 @@ impl VM
         if state == 0 {
             Ok(Value::from(result))
         } else {
-            Err(ErrorKind::Exception(self.consume_exception()))
+            Err(ErrorKind::Exception(self.consume_exception().into()))
         }
     }
 }
 
+/// The state shared with a function called through Ruby.
+struct ProtectData<F, T> {
+    f: Option<F>,
+    result: Option<std::thread::Result<T>>,
+}
+
 impl std::ops::Drop for VM
 {
     fn drop(&mut self) {
@@ -182,6 +509,7 @@ impl std::ops::Drop for VM
             VM_EXISTS = false;
             ffi::ruby_cleanup(0);
         };
+        *VM_THREAD.lock().unwrap() = None;
     }
 }
 
@@ -189,8 +517,228 @@ impl fmt::Debug for ErrorKind {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ErrorKind::VM(ref msg) => write!(fmt, "virtual machine error: {}", msg),
-            ErrorKind::Exception(e) => write!(fmt, "{}: {:?}", e.class_name(), e),
+            ErrorKind::Exception(ref e) => write!(fmt, "{:?}", e),
+            ErrorKind::Conversion(ref e) => write!(fmt, "conversion error: {}", e),
+            ErrorKind::Jump(tag) => write!(fmt, "non-local jump (tag {})", tag),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::VM(ref msg) => write!(fmt, "virtual machine error: {}", msg),
+            ErrorKind::Exception(ref e) => write!(fmt, "{}", e),
+            ErrorKind::Conversion(ref e) => write!(fmt, "{}", e),
+            ErrorKind::Jump(..) => write!(fmt, "non-local jump"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorKind {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            ErrorKind::VM(..) | ErrorKind::Jump(..) => None,
+            ErrorKind::Exception(ref e) => Some(e),
+            ErrorKind::Conversion(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<ErrorKind> for OwnedError {
+    fn from(e: ErrorKind) -> Self {
+        match e {
+            ErrorKind::VM(message) => OwnedError::VM(message),
+            ErrorKind::Exception(exception) => OwnedError::Exception(exception.into_details()),
+            ErrorKind::Conversion(e) => OwnedError::Conversion(e),
+            ErrorKind::Jump(tag) => OwnedError::Jump(tag),
+        }
+    }
+}
+
+impl fmt::Display for OwnedError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OwnedError::VM(ref msg) => write!(fmt, "virtual machine error: {}", msg),
+            OwnedError::Exception(ref e) => write!(fmt, "{}", e),
+            OwnedError::Conversion(ref e) => write!(fmt, "{}", e),
+            OwnedError::Jump(..) => write!(fmt, "non-local jump"),
+        }
+    }
+}
+
+impl std::error::Error for OwnedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            OwnedError::VM(..) | OwnedError::Jump(..) => None,
+            OwnedError::Exception(ref e) => Some(e),
+            OwnedError::Conversion(ref e) => Some(e),
+        }
+    }
+}
+
+impl VMBuilder
+{
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        VMBuilder {
+            program_name: "ruby".to_owned(),
+            load_paths: Vec::new(),
+            warning_level: None,
+            external_encoding: None,
+            internal_encoding: None,
+            gems: true,
+            flags: Vec::new(),
+        }
+    }
+
+    /// Sets the program name, used for `$0`.
+    pub fn program_name<S>(mut self, name: S) -> Self where S: Into<String> {
+        self.program_name = name.into();
+        self
+    }
+
+    /// Adds a directory to `$LOAD_PATH`.
+    ///
+    /// The standard library directories are always in the load path.
+    pub fn load_path<S>(mut self, path: S) -> Self where S: Into<String> {
+        self.load_paths.push(path.into());
+        self
+    }
+
+    /// Sets the warning level, like `-W`.
+    ///
+    /// `0` silences warnings, `1` is the default, and `2` is verbose.
+    pub fn warning_level(mut self, level: u8) -> Self {
+        self.warning_level = Some(level);
+        self
+    }
+
+    /// Sets `Encoding.default_external`.
+    pub fn external_encoding<S>(mut self, encoding: S) -> Self where S: Into<String> {
+        self.external_encoding = Some(encoding.into());
+        self
+    }
+
+    /// Sets `Encoding.default_internal`.
+    pub fn internal_encoding<S>(mut self, encoding: S) -> Self where S: Into<String> {
+        self.internal_encoding = Some(encoding.into());
+        self
+    }
+
+    /// Stops RubyGems from being loaded, like `--disable-gems`.
+    pub fn disable_gems(mut self) -> Self {
+        self.gems = false;
+        self
+    }
+
+    /// Adds a raw command line flag, such as `-rset` or `--jit`.
+    pub fn flag<S>(mut self, flag: S) -> Self where S: Into<String> {
+        self.flags.push(flag.into());
+        self
+    }
+
+    /// Adds flags from a `RUBYOPT`-style string of whitespace separated flags.
+    pub fn rubyopt(mut self, options: &str) -> Self {
+        self.flags.extend(options.split_whitespace().map(str::to_owned));
+        self
+    }
+
+    /// Creates the VM.
+    pub fn build(self) -> Result<VM, ErrorKind> {
+        unsafe {
+            if VM_EXISTS {
+                return Err(ErrorKind::VM("can only have one Ruby VM at a time".to_owned()));
+            }
+            ffi::check_api_version().map_err(ErrorKind::VM)?;
+
+            ffi::ruby_init();
+
+            let args: Vec<_> = self.arguments().iter().map(|arg| util::c_string(arg)).collect();
+            let mut arg_ptrs: Vec<_> = args.iter().map(|arg| arg.as_ptr() as *mut libc::c_char).collect();
+
+            // Processes the options, sets up the load path, and compiles an empty program.
+            let node = ffi::ruby_options(arg_ptrs.len() as libc::c_int, arg_ptrs.as_mut_ptr());
+            // Ruby can keep pointers to the arguments around.
+            std::mem::forget(args);
+
+            let mut status: libc::c_int = 0;
+            if ffi::ruby_executable_node(node, &mut status) == 0 {
+                return Err(ErrorKind::VM(format!("invalid Ruby options (status {})", status)));
+            }
+
+            ffi::ruby_script(util::c_string(&self.program_name).as_ptr());
+            VM_EXISTS = true;
+            *VM_THREAD.lock().unwrap() = Some(thread::current().id());
+
+            Ok(VM)
+        }
+    }
+
+    /// The equivalent `ruby` command line arguments.
+    fn arguments(&self) -> Vec<String> {
+        let mut args = vec![self.program_name.clone()];
+
+        if let Some(level) = self.warning_level {
+            args.push(format!("-W{}", level));
+        }
+        if let Some(ref encoding) = self.external_encoding {
+            args.push(format!("--external-encoding={}", encoding));
+        }
+        if let Some(ref encoding) = self.internal_encoding {
+            args.push(format!("--internal-encoding={}", encoding));
+        }
+        if !self.gems {
+            args.push("--disable-gems".to_owned());
+        }
+
+        args.extend(self.load_paths.iter().map(|path| format!("-I{}", path)));
+        args.extend(self.flags.iter().cloned());
+        args.extend(vec!["-e".to_owned(), String::new()]);
+        args
+    }
+}
+
+impl Default for VMBuilder
+{
+    fn default() -> Self { VMBuilder::new() }
+}
+
+impl ErrorKind {
+    /// Recovers a Rust error from a raised exception.
+    ///
+    /// Returns `None` if this is not an exception of that type.
+    pub fn downcast<E>(&self) -> Option<E> where E: RubyError {
+        match *self {
+            ErrorKind::Exception(ref exception) => E::from_exception(exception),
+            _ => None,
         }
     }
 }
 
+impl From<ConversionError> for ErrorKind {
+    fn from(e: ConversionError) -> Self {
+        ErrorKind::Conversion(e)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::VMBuilder;
+
+    #[test]
+    fn builds_command_line_arguments() {
+        let builder = VMBuilder::new()
+            .program_name("host")
+            .warning_level(0)
+            .external_encoding("UTF-8")
+            .disable_gems()
+            .load_path("/opt/plugins")
+            .rubyopt("-rset  --jit");
+
+        assert_eq!(vec!["host", "-W0", "--external-encoding=UTF-8", "--disable-gems",
+                        "-I/opt/plugins", "-rset", "--jit", "-e", ""],
+                   builder.arguments());
+    }
+}
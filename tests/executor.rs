@@ -0,0 +1,58 @@
+//! The executor starts its own VM, so it is tested in its own process.
+
+use rurust::{OwnedError, VmExecutor};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) { self.0.unpark(); }
+}
+
+fn block_on<F>(mut future: F) -> F::Output where F: Future + Unpin {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn submits_runs_and_shuts_down() {
+    let executor = VmExecutor::new().unwrap();
+    let handle = executor.handle();
+
+    assert_eq!(3, handle.run(|vm| vm.eval("1 + 2").unwrap().to_i64()));
+
+    let other = handle.clone();
+    let from_thread = thread::spawn(move || other.run(|vm| vm.eval("[4, 5].sum").unwrap().to_i64()));
+    assert_eq!(9, from_thread.join().unwrap());
+
+    // Errors are sent back with their exception captured as Rust values.
+    match handle.run(|vm| vm.eval("raise IOError, 'closed'").map(|_| ()).map_err(OwnedError::from)) {
+        Err(OwnedError::Exception(e)) => assert_eq!("IOError: closed", e.to_string()),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let future = handle.run_async(|vm| vm.eval("'done'").unwrap().as_string().unwrap());
+    assert_eq!("done", block_on(future).unwrap());
+
+    // Work submitted before the executor is dropped still runs.
+    let queued = handle.run_async(|vm| vm.eval("6 * 7").unwrap().to_i64());
+    drop(executor);
+    assert_eq!(42, block_on(queued).unwrap());
+
+    match block_on(handle.run_async(|_| ())) {
+        Err(OwnedError::VM(message)) => assert_eq!("executor shut down", message),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
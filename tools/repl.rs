@@ -26,7 +26,10 @@ fn main() {
 
 
         let abc = vm.class("Abc").
-            method("thing", thing as *mut _, 1).
+            method_fn("thing", |self_obj, args| {
+                println!("from rust: {:?} with {:?}", self_obj, args);
+                Ok(rurust::Value::nil())
+            }).
             singleton_method("hello", thing as *mut _, 1).
             build();
 
@@ -43,15 +46,22 @@ fn main() {
 
                 println!("=> {}", val);
             },
-            Err(rurust::ErrorKind::Exception(ref value)) => {
-                let ty = value.class();
-                println!("{}: {}", ty, value);
+            Err(rurust::ErrorKind::Exception(ref e)) => {
+                println!("{}", e);
+                continue;
+            },
+            Err(rurust::ErrorKind::Conversion(ref e)) => {
+                println!("Conversion error: {}", e);
                 continue;
             },
             Err(rurust::ErrorKind::VM(ref message)) => {
                 println!("Internal VM error: {}", message);
                 return;
             },
+            Err(rurust::ErrorKind::Jump(..)) => {
+                println!("Unexpected non-local jump");
+                continue;
+            },
         };
     }
 }